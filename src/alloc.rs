@@ -0,0 +1,234 @@
+use crate::raw::{self, page_size};
+use std::alloc::{GlobalAlloc, Layout, System};
+
+// The length of the mapping backing an allocation, or `None` if it can't be mapped.
+fn mapped_len(layout: &Layout) -> Option<usize> {
+    if layout.align() > page_size() {
+        return None;
+    }
+    raw::round_up_to_page(layout.size()).ok()
+}
+
+/// A global allocator that maps each allocation directly to anonymous memory.
+///
+/// Memory is zeroed when mapped, so [`alloc_zeroed`](`GlobalAlloc::alloc_zeroed`) is free.
+/// On Linux, [`realloc`](`GlobalAlloc::realloc`) remaps pages rather than copying them.
+/// Since every allocation is rounded up to the page size, this is intended for large
+/// allocations; see [`ThresholdAlloc`] for combining it with another allocator.
+/// Alignments larger than the page size are not supported.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MmapAlloc;
+
+unsafe impl GlobalAlloc for MmapAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match mapped_len(&layout) {
+            Some(len) => raw::allocate(len, false).unwrap_or(core::ptr::null_mut()),
+            None => core::ptr::null_mut(),
+        }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if let Some(len) = mapped_len(&layout) {
+            raw::deallocate(ptr, len)
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let (old_len, new_len) = match (mapped_len(&layout), mapped_len(&new_layout)) {
+            (Some(old_len), Some(new_len)) => (old_len, new_len),
+            _ => return core::ptr::null_mut(),
+        };
+        if old_len == new_len {
+            return ptr;
+        }
+        if let Ok(ptr) = raw::reallocate(ptr, old_len, new_len) {
+            return ptr;
+        }
+
+        let new_ptr = self.alloc(new_layout);
+        if !new_ptr.is_null() {
+            core::ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+            raw::deallocate(ptr, old_len);
+        }
+        new_ptr
+    }
+}
+
+/// A global allocator that serves large allocations with [`MmapAlloc`], and delegates smaller
+/// allocations to another allocator.
+///
+/// Allocations move between the allocators when reallocated across the threshold.
+#[derive(Copy, Clone, Debug)]
+pub struct ThresholdAlloc<A = System> {
+    threshold: usize,
+    small: A,
+}
+
+impl ThresholdAlloc {
+    /// Serve allocations of at least `threshold` bytes with [`MmapAlloc`], and the rest with
+    /// [`System`].
+    pub const fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            small: System,
+        }
+    }
+}
+
+impl<A> ThresholdAlloc<A> {
+    /// Serve allocations of at least `threshold` bytes with [`MmapAlloc`], and the rest with
+    /// `small`.
+    pub const fn with_allocator(threshold: usize, small: A) -> Self {
+        Self { threshold, small }
+    }
+
+    /// The size of the smallest allocation served by [`MmapAlloc`].
+    pub const fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    fn is_large(&self, layout: &Layout) -> bool {
+        layout.size() >= self.threshold && layout.align() <= page_size()
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for ThresholdAlloc<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if self.is_large(&layout) {
+            MmapAlloc.alloc(layout)
+        } else {
+            self.small.alloc(layout)
+        }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        if self.is_large(&layout) {
+            MmapAlloc.alloc_zeroed(layout)
+        } else {
+            self.small.alloc_zeroed(layout)
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if self.is_large(&layout) {
+            MmapAlloc.dealloc(ptr, layout)
+        } else {
+            self.small.dealloc(ptr, layout)
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        match (self.is_large(&layout), self.is_large(&new_layout)) {
+            (true, true) => MmapAlloc.realloc(ptr, layout, new_size),
+            (false, false) => self.small.realloc(ptr, layout, new_size),
+            _ => {
+                let new_ptr = self.alloc(new_layout);
+                if !new_ptr.is_null() {
+                    core::ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+                    self.dealloc(ptr, layout);
+                }
+                new_ptr
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fill the allocation with a pattern, which `check` verifies after it moves.
+    unsafe fn fill(ptr: *mut u8, len: usize) {
+        for i in 0..len {
+            ptr.add(i).write(i as u8);
+        }
+    }
+
+    unsafe fn check(ptr: *const u8, len: usize) {
+        for i in 0..len {
+            assert_eq!(ptr.add(i).read(), i as u8);
+        }
+    }
+
+    #[test]
+    fn realloc_across_threshold() {
+        let alloc = ThresholdAlloc::new(page_size());
+        let small = Layout::from_size_align(100, 8).unwrap();
+        let large_size = 3 * page_size() + 1;
+        unsafe {
+            let ptr = alloc.alloc(small);
+            assert!(!ptr.is_null());
+            fill(ptr, small.size());
+
+            // Grow into the mapped allocator
+            let ptr = alloc.realloc(ptr, small, large_size);
+            assert!(!ptr.is_null());
+            check(ptr, small.size());
+            fill(ptr, large_size);
+
+            // Grow and shrink within the mapped allocator
+            let large = Layout::from_size_align(large_size, 8).unwrap();
+            let ptr = alloc.realloc(ptr, large, 2 * large_size);
+            assert!(!ptr.is_null());
+            check(ptr, large_size);
+            let large = Layout::from_size_align(2 * large_size, 8).unwrap();
+            let ptr = alloc.realloc(ptr, large, page_size());
+            assert!(!ptr.is_null());
+            check(ptr, page_size());
+
+            // Shrink back into the small allocator
+            let large = Layout::from_size_align(page_size(), 8).unwrap();
+            let ptr = alloc.realloc(ptr, large, small.size());
+            assert!(!ptr.is_null());
+            check(ptr, small.size());
+            alloc.dealloc(ptr, small);
+        }
+    }
+
+    #[test]
+    fn zeroed() {
+        let alloc = ThresholdAlloc::new(page_size());
+        for size in [10, page_size(), 5 * page_size() + 3] {
+            let layout = Layout::from_size_align(size, 1).unwrap();
+            unsafe {
+                let ptr = alloc.alloc_zeroed(layout);
+                assert!(!ptr.is_null());
+                assert!(std::slice::from_raw_parts(ptr, size)
+                    .iter()
+                    .all(|b| *b == 0));
+                alloc.dealloc(ptr, layout);
+            }
+        }
+    }
+
+    #[test]
+    fn over_aligned() {
+        let align = 2 * page_size();
+        let layout = Layout::from_size_align(4 * page_size(), align).unwrap();
+        unsafe {
+            // Unsupported by the mapped allocator alone
+            assert!(MmapAlloc.alloc(layout).is_null());
+
+            // Delegated to the small allocator, even when above the threshold
+            let alloc = ThresholdAlloc::new(page_size());
+            let ptr = alloc.alloc(layout);
+            assert!(!ptr.is_null());
+            assert_eq!(ptr as usize % align, 0);
+            fill(ptr, layout.size());
+            let ptr = alloc.realloc(ptr, layout, 8 * page_size());
+            assert!(!ptr.is_null());
+            assert_eq!(ptr as usize % align, 0);
+            check(ptr, layout.size());
+            alloc.dealloc(
+                ptr,
+                Layout::from_size_align(8 * page_size(), align).unwrap(),
+            );
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use crate::raw::{self, Length, Reservation};
+use std::{alloc::Layout, cell::Cell, io::Error, ptr::NonNull};
+
+/// A bump allocator backed by a reserved region of address space.
+///
+/// Allocations are made by advancing a cursor through the region, and memory is committed as
+/// the cursor advances.
+/// Allocated values are never dropped, and all memory is released at once by
+/// [`reset`](`Self::reset`).
+///
+/// Since memory is never reused before it is decommitted, it is always zeroed when allocated, so
+/// [`alloc_zeroed`](`Self::alloc_zeroed`) is free.
+pub struct Arena {
+    reservation: Reservation,
+    position: Cell<usize>,
+    committed: Cell<usize>,
+}
+
+// Each allocation is disjoint, so handing out mutable references from a shared reference is sound
+#[allow(clippy::mut_from_ref)]
+impl Arena {
+    /// Create an empty arena.
+    ///
+    /// The arena can hold at least `capacity` bytes.
+    /// Only address space is reserved, so the capacity may be very large.
+    pub fn new(capacity: usize) -> Result<Self, Error> {
+        Ok(Self {
+            reservation: Reservation::new(Length::round_up(capacity.max(1))?)?,
+            position: Cell::new(0),
+            committed: Cell::new(0),
+        })
+    }
+
+    /// The number of bytes the arena can hold.
+    pub fn capacity(&self) -> usize {
+        self.reservation.len()
+    }
+
+    /// The number of bytes allocated, including padding for alignment.
+    pub fn allocated(&self) -> usize {
+        self.position.get()
+    }
+
+    /// Allocate zeroed memory for the layout.
+    ///
+    /// Returns an error if the arena is full, or memory can't be committed.
+    pub fn alloc_layout(&self, layout: Layout) -> Result<NonNull<u8>, Error> {
+        let base = self.reservation.as_mut_ptr() as usize;
+        let position = self.position.get();
+        let overflow = || raw::Error::OutOfBounds {
+            end: (position as u64).saturating_add(layout.size() as u64),
+            size: self.capacity() as u64,
+        };
+        let start = (base + position)
+            .checked_add(layout.align() - 1)
+            .ok_or_else(overflow)?
+            / layout.align()
+            * layout.align()
+            - base;
+        let end = start
+            .checked_add(layout.size())
+            .filter(|end| *end <= self.capacity())
+            .ok_or_else(overflow)?;
+
+        let committed = self.committed.get();
+        if end > committed {
+            let end = raw::round_up_to_page(end)?;
+            // Safety: the range is past every allocation, and views are never mapped
+            unsafe { self.reservation.commit(committed..end)? };
+            self.committed.set(end);
+        }
+        self.position.set(end);
+        // Safety: the allocation is within the reservation, which is never null
+        Ok(unsafe { NonNull::new_unchecked(self.reservation.as_mut_ptr().add(start)) })
+    }
+
+    /// Allocate a value.
+    ///
+    /// The value is never dropped.
+    pub fn alloc<T>(&self, value: T) -> Result<&mut T, Error> {
+        let ptr = self.alloc_layout(Layout::new::<T>())?.as_ptr() as *mut T;
+        // Safety: the memory is allocated for the value, and is not aliased
+        unsafe {
+            ptr.write(value);
+            Ok(&mut *ptr)
+        }
+    }
+
+    /// Allocate a zeroed value.
+    ///
+    /// This doesn't write to memory, since newly allocated memory is always zeroed.
+    pub fn alloc_zeroed<T: crate::ZeroInit>(&self) -> Result<&mut T, Error> {
+        let ptr = self.alloc_layout(Layout::new::<T>())?.as_ptr() as *mut T;
+        // Safety: the memory is zeroed, which is a valid value
+        Ok(unsafe { &mut *ptr })
+    }
+
+    /// Allocate a slice of `len` zeroed values.
+    ///
+    /// This doesn't write to memory, since newly allocated memory is always zeroed.
+    pub fn alloc_slice_zeroed<T: crate::ZeroInit>(&self, len: usize) -> Result<&mut [T], Error> {
+        let layout = Layout::array::<T>(len).map_err(|_| raw::Error::Overflow)?;
+        let ptr = self.alloc_layout(layout)?.as_ptr() as *mut T;
+        // Safety: the memory is zeroed, which is a valid value
+        Ok(unsafe { std::slice::from_raw_parts_mut(ptr, len) })
+    }
+
+    /// Allocate a copy of a slice.
+    pub fn alloc_slice_copy<T: Copy>(&self, values: &[T]) -> Result<&mut [T], Error> {
+        let layout = Layout::for_value(values);
+        let ptr = self.alloc_layout(layout)?.as_ptr() as *mut T;
+        // Safety: the memory is allocated for the values, and is not aliased
+        unsafe {
+            std::ptr::copy_nonoverlapping(values.as_ptr(), ptr, values.len());
+            Ok(std::slice::from_raw_parts_mut(ptr, values.len()))
+        }
+    }
+
+    /// Free all allocations, releasing the committed memory.
+    ///
+    /// If the memory can't be released, the allocations are kept.
+    pub fn reset(&mut self) -> Result<(), Error> {
+        // Safety: the arena is uniquely borrowed, so no allocations are referenced
+        unsafe { self.reservation.decommit(0..self.committed.get())? };
+        self.position.set(0);
+        self.committed.set(0);
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Arena {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Arena")
+            .field("capacity", &self.capacity())
+            .field("allocated", &self.allocated())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::page_size;
+
+    #[test]
+    fn over_aligned() {
+        let align = 4 * page_size();
+        let arena = Arena::new(16 * page_size()).unwrap();
+        arena.alloc(1u8).unwrap();
+        let layout = Layout::from_size_align(1, align).unwrap();
+        let ptr = arena.alloc_layout(layout).unwrap();
+        assert_eq!(ptr.as_ptr() as usize % align, 0);
+        // Safety: the allocation is one byte
+        unsafe { ptr.as_ptr().write(1) };
+        assert!(arena.allocated() > page_size());
+    }
+
+    #[test]
+    fn exhausted() {
+        let arena = Arena::new(page_size()).unwrap();
+        let capacity = arena.capacity();
+        arena.alloc_slice_zeroed::<u8>(capacity - 1).unwrap();
+        arena.alloc(1u8).unwrap();
+        let err = arena.alloc(1u8).unwrap_err();
+        let err = err
+            .get_ref()
+            .and_then(|err| err.downcast_ref::<raw::Error>());
+        assert!(matches!(err, Some(raw::Error::OutOfBounds { .. })));
+        assert!(arena.alloc_slice_zeroed::<u64>(usize::MAX / 8).is_err());
+        assert_eq!(arena.allocated(), capacity);
+    }
+
+    #[test]
+    fn reset() {
+        let len = 3 * page_size() + 5;
+        let mut arena = Arena::new(8 * page_size()).unwrap();
+        let slice = arena.alloc_slice_zeroed::<u8>(len).unwrap();
+        assert!(slice.iter().all(|x| *x == 0));
+        slice.fill(1);
+        *arena.alloc_zeroed::<u32>().unwrap() = 2;
+
+        arena.reset().unwrap();
+        assert_eq!(arena.allocated(), 0);
+        let slice = arena.alloc_slice_zeroed::<u8>(len).unwrap();
+        assert!(slice.iter().all(|x| *x == 0));
+        assert_eq!(*arena.alloc_zeroed::<u32>().unwrap(), 0);
+        assert_eq!(arena.alloc_slice_copy(&[1, 2, 3]).unwrap(), &[1, 2, 3]);
+    }
+}
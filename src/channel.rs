@@ -0,0 +1,247 @@
+use crate::raw::{FilePermissions, HugePageSize, Object};
+use std::{
+    io::{Error, ErrorKind, Read},
+    os::unix::{
+        io::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        net::UnixStream,
+    },
+};
+
+// The size, a permission byte, and a huge page size byte, padded.
+const HEADER_LEN: usize = 16;
+const WRITE: u8 = 1;
+const EXECUTE: u8 = 2;
+
+// Large enough for a control message holding a single file descriptor.
+const CONTROL_LEN: usize = 4;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const MSG_CMSG_CLOEXEC: libc::c_int = libc::MSG_CMSG_CLOEXEC;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const MSG_CMSG_CLOEXEC: libc::c_int = 0;
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn encode(object: &Object) -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..8].copy_from_slice(&object.size().to_ne_bytes());
+    let permissions = object.permissions();
+    if permissions.write {
+        header[8] |= WRITE;
+    }
+    if permissions.execute {
+        header[8] |= EXECUTE;
+    }
+    header[9] = match object.huge_page_size() {
+        None => 0,
+        Some(HugePageSize::Size2MiB) => 1,
+        Some(HugePageSize::Size1GiB) => 2,
+    };
+    header
+}
+
+fn control_space() -> usize {
+    // Safety: always safe to call
+    let space = unsafe { libc::CMSG_SPACE(core::mem::size_of::<RawFd>() as _) } as usize;
+    assert!(space <= CONTROL_LEN * core::mem::size_of::<u64>());
+    space
+}
+
+/// A channel for passing objects between processes over a Unix domain socket.
+///
+/// The object's file descriptor is sent with `SCM_RIGHTS`, along with its size and permissions,
+/// so the receiving process gets an equivalent [`Object`] that shares the same memory.
+/// The size and huge page size are checked against the received file descriptor.
+/// Received objects are never sparse, since views past the end of the file can't be validated.
+#[derive(Debug)]
+pub struct ObjectChannel {
+    stream: UnixStream,
+}
+
+impl ObjectChannel {
+    /// Create a channel over a connected socket.
+    pub fn new(stream: UnixStream) -> Self {
+        Self { stream }
+    }
+
+    /// Create a pair of connected channels.
+    ///
+    /// One of the channels is typically inherited by a child process.
+    pub fn pair() -> Result<(Self, Self), Error> {
+        let (a, b) = UnixStream::pair()?;
+        Ok((Self::new(a), Self::new(b)))
+    }
+
+    /// The underlying socket.
+    pub fn get_ref(&self) -> &UnixStream {
+        &self.stream
+    }
+
+    /// Unwrap the underlying socket.
+    pub fn into_inner(self) -> UnixStream {
+        self.stream
+    }
+
+    /// Send an object to the other end of the channel.
+    pub fn send(&self, object: &Object) -> Result<(), Error> {
+        self.send_header(&encode(object), object.as_raw_fd())
+    }
+
+    fn send_header(&self, header: &[u8; HEADER_LEN], fd: RawFd) -> Result<(), Error> {
+        let mut iov = libc::iovec {
+            iov_base: header.as_ptr() as *mut _,
+            iov_len: header.len(),
+        };
+        let mut control = [0u64; CONTROL_LEN];
+        // Safety: the message refers to valid buffers, and the control message fits within the
+        // control buffer
+        let sent = unsafe {
+            let mut msg: libc::msghdr = core::mem::zeroed();
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.as_mut_ptr() as *mut _;
+            msg.msg_controllen = control_space() as _;
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(core::mem::size_of::<RawFd>() as _) as _;
+            core::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, fd);
+            loop {
+                let sent = libc::sendmsg(self.stream.as_raw_fd(), &msg, 0);
+                if sent >= 0 {
+                    break sent as usize;
+                }
+                let err = Error::last_os_error();
+                if err.kind() != ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+        };
+
+        // The file descriptor is attached to the first byte, so the rest can be sent normally
+        std::io::Write::write_all(&mut &self.stream, &header[sent..])
+    }
+
+    /// Receive an object from the other end of the channel.
+    ///
+    /// Returns an error with [`ErrorKind::UnexpectedEof`] if the other end is closed.
+    ///
+    /// # Safety
+    /// The sending process can continue to modify or truncate the object.
+    /// See [`FileOptions::new`](`crate::raw::FileOptions::new`) for the consequences.
+    pub unsafe fn recv(&self) -> Result<Object, Error> {
+        let mut header = [0u8; HEADER_LEN];
+        let mut iov = libc::iovec {
+            iov_base: header.as_mut_ptr() as *mut _,
+            iov_len: header.len(),
+        };
+        let mut control = [0u64; CONTROL_LEN];
+        let mut msg: libc::msghdr = core::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut _;
+        msg.msg_controllen = control_space() as _;
+        let received = loop {
+            let received = libc::recvmsg(self.stream.as_raw_fd(), &mut msg, MSG_CMSG_CLOEXEC);
+            if received >= 0 {
+                break received as usize;
+            }
+            let err = Error::last_os_error();
+            if err.kind() != ErrorKind::Interrupted {
+                return Err(err);
+            }
+        };
+        if received == 0 {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+
+        // Take ownership of every received file descriptor, so they are closed on error
+        let mut fds = Vec::new();
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg) as *const RawFd;
+                let len = (*cmsg).cmsg_len as usize - (data as usize - cmsg as usize);
+                for i in 0..len / core::mem::size_of::<RawFd>() {
+                    fds.push(OwnedFd::from_raw_fd(core::ptr::read_unaligned(data.add(i))));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+        if MSG_CMSG_CLOEXEC == 0 {
+            for fd in &fds {
+                libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+        }
+        if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+            return Err(invalid_data("too many file descriptors received"));
+        }
+        (&self.stream).read_exact(&mut header[received..])?;
+
+        let fd = match (fds.pop(), fds.is_empty()) {
+            (Some(fd), true) => fd,
+            _ => return Err(invalid_data("expected a single file descriptor")),
+        };
+        let mut size = [0; 8];
+        size.copy_from_slice(&header[..8]);
+        let permissions = FilePermissions {
+            write: header[8] & WRITE != 0,
+            execute: header[8] & EXECUTE != 0,
+        };
+        let huge_pages = match header[9] {
+            0 => None,
+            1 => Some(HugePageSize::Size2MiB),
+            2 => Some(HugePageSize::Size1GiB),
+            _ => return Err(invalid_data("unknown huge page size")),
+        };
+
+        // The size and huge pages are determined from the file descriptor itself
+        let object = Object::from_owned_fd(fd, permissions)?;
+        if object.size() != u64::from_ne_bytes(size) || object.huge_page_size() != huge_pages {
+            return Err(invalid_data("header doesn't match the received object"));
+        }
+        Ok(object)
+    }
+}
+
+impl From<UnixStream> for ObjectChannel {
+    fn from(stream: UnixStream) -> Self {
+        Self::new(stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::ReadPermissions;
+
+    #[test]
+    fn round_trip() {
+        let (a, b) = ObjectChannel::pair().unwrap();
+        let object = Object::anonymous(100, ReadPermissions::Read).unwrap();
+        a.send(&object).unwrap();
+        // Safety: the object isn't truncated
+        let received = unsafe { b.recv() }.unwrap();
+        assert_eq!(received.size(), 100);
+        assert_eq!(received.permissions(), object.permissions());
+
+        drop(a);
+        // Safety: no object is received
+        let err = unsafe { b.recv() }.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn mismatched_header() {
+        let (a, b) = ObjectChannel::pair().unwrap();
+        let object = Object::anonymous(100, ReadPermissions::Read).unwrap();
+        let mut header = encode(&object);
+        header[..8].copy_from_slice(&(1u64 << 40).to_ne_bytes());
+        a.send_header(&header, object.as_raw_fd()).unwrap();
+        // Safety: the object isn't truncated
+        let err = unsafe { b.recv() }.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
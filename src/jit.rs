@@ -0,0 +1,173 @@
+use crate::raw::{
+    self, map, map_mut, Length, Mapping, MappingMut, Object, Offset, ReadPermissions,
+    WritePermissions,
+};
+use std::{io::Error, ops::Range};
+
+/// A buffer for generating executable code.
+///
+/// The buffer is backed by a single object that is mapped twice: once writable, and once
+/// executable at a different address.
+/// Memory is therefore never both writable and executable at the same address, and permissions
+/// never need to be changed.
+pub struct JitBuffer {
+    code: Mapping<'static>,
+    data: MappingMut<'static>,
+    position: usize,
+}
+
+impl JitBuffer {
+    /// Create a buffer.
+    ///
+    /// The resulting buffer can hold at least `min_size` bytes of code.
+    pub fn new(min_size: usize) -> Result<Self, Error> {
+        let offset = Offset::exact(0)?;
+        let length = Length::round_up(min_size)?;
+        let object = Object::anonymous(length.to_usize(), ReadPermissions::Execute)?;
+        // Safety: the code mapping is only accessed through raw pointers, and the object is never
+        // modified after it is dropped
+        unsafe {
+            let data = map_mut(&object.view_mut(offset, length, WritePermissions::Write)?)?;
+            let code = map(&object.view(offset, length, ReadPermissions::Execute)?)?;
+            Ok(Self {
+                code: code.detach(),
+                data: data.detach(),
+                position: 0,
+            })
+        }
+    }
+
+    /// The number of bytes of code the buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
+    /// The offset where the next code will be emitted.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Move the emit cursor.
+    ///
+    /// # Panics
+    /// Panics if `position` is larger than the capacity.
+    pub fn set_position(&mut self, position: usize) {
+        assert!(position <= self.capacity(), "position exceeds the capacity");
+        self.position = position;
+    }
+
+    /// Write code at the emit cursor, and advance the cursor past it.
+    ///
+    /// Returns the offset of the emitted code, or `None` if there is not enough space.
+    pub fn emit(&mut self, code: &[u8]) -> Option<usize> {
+        let position = self.position;
+        let end = position.checked_add(code.len())?;
+        self.data.get_mut(position..end)?.copy_from_slice(code);
+        self.position = end;
+        Some(position)
+    }
+
+    /// The writable view of the buffer.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Returns a pointer to executable code at `offset`.
+    ///
+    /// # Panics
+    /// Panics if `offset` is not less than the capacity.
+    pub fn code_ptr(&self, offset: usize) -> *const u8 {
+        assert!(offset < self.capacity(), "offset exceeds the capacity");
+        // Safety: the offset is within the mapping
+        unsafe { self.code.as_ptr().add(offset) }
+    }
+
+    /// Flush the instruction cache for a range of code.
+    ///
+    /// This must be called after emitting code, before it is executed.
+    ///
+    /// # Panics
+    /// Panics if the range extends past the capacity.
+    pub fn flush_instruction_cache(&self, range: Range<usize>) {
+        assert!(
+            range.start <= range.end && range.end <= self.capacity(),
+            "range exceeds the capacity"
+        );
+        // Safety: the range is within the mapping
+        unsafe {
+            raw::flush_instruction_cache(self.code.as_ptr().add(range.start), range.len());
+        }
+    }
+
+    /// Returns a function pointer to executable code at `offset`.
+    ///
+    /// # Safety
+    /// * `F` must be a function pointer type.
+    /// * The code at `offset` must be a valid function with the signature of `F`, and the
+    ///   instruction cache must have been flushed after it was emitted.
+    /// * The function pointer must not be called after the buffer is dropped or the code is
+    ///   modified.
+    ///
+    /// # Panics
+    /// Panics if `F` is not pointer-sized, or if `offset` is not less than the capacity.
+    pub unsafe fn function<F: Copy>(&self, offset: usize) -> F {
+        assert_eq!(
+            std::mem::size_of::<F>(),
+            std::mem::size_of::<*const u8>(),
+            "function pointers must be pointer-sized"
+        );
+        let ptr = self.code_ptr(offset);
+        std::mem::transmute_copy(&ptr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emit() {
+        let mut buffer = JitBuffer::new(1).unwrap();
+        let capacity = buffer.capacity();
+        assert!(capacity >= 1);
+        assert_eq!(buffer.emit(&[1, 2, 3]), Some(0));
+        assert_eq!(buffer.emit(&[4]), Some(3));
+        assert_eq!(buffer.position(), 4);
+        assert_eq!(&buffer.as_mut_slice()[..4], &[1, 2, 3, 4]);
+
+        // Emitting past the capacity fails without writing or moving the cursor
+        buffer.set_position(capacity - 2);
+        assert_eq!(buffer.emit(&[5, 6, 7]), None);
+        assert_eq!(buffer.position(), capacity - 2);
+        assert_eq!(&buffer.as_mut_slice()[capacity - 2..], &[0, 0]);
+        assert_eq!(buffer.emit(&[5, 6]), Some(capacity - 2));
+        assert_eq!(buffer.emit(&[]), Some(capacity));
+        assert_eq!(buffer.emit(&[8]), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "position exceeds the capacity")]
+    fn set_position_past_capacity() {
+        let mut buffer = JitBuffer::new(1).unwrap();
+        let capacity = buffer.capacity();
+        buffer.set_position(capacity + 1);
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    #[test]
+    fn call() {
+        // Returns 42
+        #[cfg(target_arch = "x86_64")]
+        const CODE: &[u8] = &[0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]; // mov eax, 42; ret
+        #[cfg(target_arch = "aarch64")]
+        const CODE: &[u8] = &[0x40, 0x05, 0x80, 0x52, 0xc0, 0x03, 0x5f, 0xd6]; // mov w0, #42; ret
+
+        let mut buffer = JitBuffer::new(1).unwrap();
+        buffer.emit(&[0; 16]).unwrap();
+        let offset = buffer.emit(CODE).unwrap();
+        buffer.flush_instruction_cache(offset..buffer.position());
+        // Safety: the code is a valid function with this signature, and the cache is flushed
+        let function = unsafe { buffer.function::<extern "C" fn() -> u32>(offset) };
+        assert_eq!(function(), 42);
+    }
+}
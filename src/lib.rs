@@ -7,3 +7,32 @@ pub use zero_init::*;
 
 mod mirror;
 pub use mirror::*;
+
+mod ring;
+pub use ring::*;
+
+mod pipe;
+pub use pipe::*;
+
+mod jit;
+pub use jit::*;
+
+mod stable_vec;
+pub use stable_vec::*;
+
+mod arena;
+pub use arena::*;
+
+mod alloc;
+pub use alloc::*;
+
+mod page_buf;
+pub use page_buf::*;
+
+mod zeroed_box;
+pub use zeroed_box::*;
+
+#[cfg(unix)]
+mod channel;
+#[cfg(unix)]
+pub use channel::*;
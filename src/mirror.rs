@@ -1,44 +1,122 @@
 use crate::raw::{
-    map_multiple_mut, unmap, Length, Object, Offset, ReadPermissions, WritePermissions,
+    self, map_multiple_mut, HugePageSize, Length, MappingMut, Object, Offset, ReadPermissions,
+    WritePermissions,
 };
 use std::io::Error;
 
-fn allocate_mirror<T>(min_size: usize) -> Result<(*mut T, usize), Error> {
-    let offset = Offset::exact(0).unwrap();
-    let min_size = (min_size + 1) / 2;
-    let length = Length::round_up(min_size * std::mem::size_of::<T>());
-    let object = Object::anonymous(length.to_usize(), ReadPermissions::Read)?;
-    let view = object
-        .view_mut(offset, length, WritePermissions::Write)
-        .unwrap();
-    map_multiple_mut(&[view; 2]).map(|(p, l)| (p as *mut T, l / std::mem::size_of::<T>()))
+fn allocate_mirror<T>(
+    min_size: usize,
+    huge_pages: Option<HugePageSize>,
+) -> Result<MappingMut<'static>, Error> {
+    let offset = Offset::exact(0)?;
+    let min_size = min_size - min_size / 2;
+    let size = Length::round_up(
+        min_size
+            .checked_mul(std::mem::size_of::<T>())
+            .ok_or(raw::Error::Overflow)?,
+    )?
+    .to_usize();
+    // Objects backed by huge pages round their size up to the huge page size
+    let object = match huge_pages {
+        Some(page_size) => Object::anonymous_huge(size, page_size, ReadPermissions::Read)?,
+        None => Object::anonymous(size, ReadPermissions::Read)?,
+    };
+    let length = Length::exact(object.size() as usize)?;
+    let view = object.view_mut(offset, length, WritePermissions::Write)?;
+    // Safety: the overlapping views are only accessed through raw pointers, and the object is
+    // never modified after it is dropped
+    unsafe { Ok(map_multiple_mut(&[view; 2])?.detach()) }
 }
 
 /// A mirrored memory region.
 ///
 /// Changes in the first half of the slice propagates to the second half, and vice versa.
+///
+/// The mirror owns the elements in the first half of the slice, which are dropped along with it.
 pub struct Mirror<T> {
-    map: *mut T,
+    map: MappingMut<'static>,
     len: usize,
+    huge_pages: Option<HugePageSize>,
+    _type: std::marker::PhantomData<T>,
 }
 
-impl<T> Drop for Mirror<T> {
-    fn drop(&mut self) {
-        let ptr = self.map as *mut u8;
-        let len = self.len * std::mem::size_of::<T>();
-        unsafe { unmap(ptr, [len / 2; 2].iter().copied()) }
-    }
-}
+// Safety: the elements are uniquely owned, like a `Vec<T>`
+unsafe impl<T: Send> Send for Mirror<T> {}
+unsafe impl<T: Sync> Sync for Mirror<T> {}
 
 impl<T> Mirror<T> {
-    unsafe fn new<F: Fn() -> T>(min_size: usize, value: Option<F>) -> Result<Self, Error> {
-        let (map, len) = allocate_mirror::<T>(min_size)?;
+    unsafe fn new<F: Fn() -> T>(
+        min_size: usize,
+        huge_pages: Option<HugePageSize>,
+        value: Option<F>,
+    ) -> Result<Self, Error> {
+        let mut map = allocate_mirror::<T>(min_size, huge_pages)?;
+        let len = map.len() / std::mem::size_of::<T>();
         if let Some(value) = value {
+            let ptr = map.as_mut_ptr() as *mut T;
             for i in 0..(len / 2) {
-                map.add(i).write(value())
+                ptr.add(i).write(value())
             }
         }
-        Ok(Self { map, len })
+        Ok(Self {
+            map,
+            len,
+            huge_pages,
+            _type: std::marker::PhantomData,
+        })
+    }
+
+    unsafe fn grow_impl<F: Fn() -> T>(
+        &mut self,
+        new_min_size: usize,
+        head: usize,
+        len: usize,
+        value: Option<F>,
+    ) -> Result<(), Error> {
+        let capacity = self.len / 2;
+        assert!(
+            head < capacity && len <= capacity,
+            "window must be within the first half of the mirror"
+        );
+
+        // Initialize the new elements before moving anything, in case initialization panics
+        let min_size = self.len.checked_add(1).ok_or(raw::Error::Overflow)?;
+        let mut map = allocate_mirror::<T>(new_min_size.max(min_size), self.huge_pages)?;
+        let new_len = map.len() / std::mem::size_of::<T>();
+        let dst = map.as_mut_ptr() as *mut T;
+        if let Some(value) = value {
+            for i in len..(new_len / 2) {
+                dst.add(i).write(value())
+            }
+        }
+
+        let src = self.map.as_mut_ptr() as *mut T;
+        std::ptr::copy_nonoverlapping(src.add(head), dst, len);
+        let old = std::mem::replace(&mut self.map, map);
+        self.len = new_len;
+
+        // The elements outside the window are no longer reachable, and are contiguous in the old
+        // mirror.
+        // If dropping one panics, the rest are still dropped, and the old mapping is unmapped
+        // while unwinding.
+        std::ptr::drop_in_place(std::ptr::slice_from_raw_parts_mut(
+            src.add(head + len),
+            capacity - len,
+        ));
+        drop(old);
+        Ok(())
+    }
+
+    /// Lock the mirror into RAM, preventing it from being swapped out.
+    ///
+    /// Growing the mirror replaces its memory, which is not locked.
+    pub fn lock(&self) -> Result<(), Error> {
+        Ok(self.map.lock(0..self.map.len())?)
+    }
+
+    /// Unlock the mirror, allowing it to be swapped out.
+    pub fn unlock(&self) -> Result<(), Error> {
+        Ok(self.map.unlock(0..self.map.len())?)
     }
 }
 
@@ -50,7 +128,32 @@ where
     ///
     /// The resulting slice has at least `min_size` elements.
     pub fn zeroed(min_size: usize) -> Result<Self, Error> {
-        unsafe { Self::new::<fn() -> T>(min_size, None) }
+        unsafe { Self::new::<fn() -> T>(min_size, None, None) }
+    }
+
+    /// Initialize with zeroed values, backed by huge pages.
+    ///
+    /// The resulting slice has at least `min_size` elements, and each half is a multiple of the
+    /// huge page size.
+    /// Growing the mirror keeps using huge pages.
+    /// See [`Object::anonymous_huge`] for availability.
+    pub fn zeroed_huge(min_size: usize, page_size: HugePageSize) -> Result<Self, Error> {
+        unsafe { Self::new::<fn() -> T>(min_size, Some(page_size), None) }
+    }
+
+    /// Grow the mirror, preserving the `len` elements starting at index `head`.
+    ///
+    /// The preserved elements are moved to the beginning of the resized slice, and the remaining
+    /// elements are zeroed.
+    /// Elements outside the preserved window are dropped.
+    /// The resulting slice has at least `new_min_size` elements, and is always larger than the
+    /// original slice.
+    ///
+    /// # Panics
+    /// Panics if the window does not start within the first half of the slice, or is longer than
+    /// half of the slice.
+    pub fn grow(&mut self, new_min_size: usize, head: usize, len: usize) -> Result<(), Error> {
+        unsafe { self.grow_impl::<fn() -> T>(new_min_size, head, len, None) }
     }
 }
 
@@ -62,7 +165,20 @@ where
     ///
     /// The resulting slice has at least `min_size` elements.
     pub fn with_default(min_size: usize) -> Result<Self, Error> {
-        unsafe { Self::new(min_size, Some(Default::default)) }
+        unsafe { Self::new(min_size, None, Some(Default::default)) }
+    }
+
+    /// Grow the mirror, preserving the `len` elements starting at index `head`.
+    ///
+    /// Like [`grow`](`Self::grow`), but the remaining elements are initialized with default
+    /// values.
+    pub fn grow_with_default(
+        &mut self,
+        new_min_size: usize,
+        head: usize,
+        len: usize,
+    ) -> Result<(), Error> {
+        unsafe { self.grow_impl(new_min_size, head, len, Some(Default::default)) }
     }
 }
 
@@ -74,7 +190,33 @@ where
     ///
     /// The resulting slice has at least `min_size` elements.
     pub fn with_value(min_size: usize, value: T) -> Result<Self, Error> {
-        unsafe { Self::new(min_size, Some(|| value.clone())) }
+        unsafe { Self::new(min_size, None, Some(|| value.clone())) }
+    }
+
+    /// Grow the mirror, preserving the `len` elements starting at index `head`.
+    ///
+    /// Like [`grow`](`Self::grow`), but the remaining elements are initialized with the provided
+    /// value.
+    pub fn grow_with_value(
+        &mut self,
+        new_min_size: usize,
+        head: usize,
+        len: usize,
+        value: T,
+    ) -> Result<(), Error> {
+        unsafe { self.grow_impl(new_min_size, head, len, Some(|| value.clone())) }
+    }
+}
+
+impl<T> Drop for Mirror<T> {
+    fn drop(&mut self) {
+        // Safety: the elements in the first half are initialized and owned by the mirror
+        unsafe {
+            std::ptr::drop_in_place(std::ptr::slice_from_raw_parts_mut(
+                self.map.as_mut_ptr() as *mut T,
+                self.len / 2,
+            ))
+        }
     }
 }
 
@@ -82,13 +224,13 @@ impl<T> std::ops::Deref for Mirror<T> {
     type Target = [T];
 
     fn deref(&self) -> &Self::Target {
-        unsafe { std::slice::from_raw_parts(self.map, self.len) }
+        unsafe { std::slice::from_raw_parts(self.map.as_ptr() as *const T, self.len) }
     }
 }
 
 impl<T> std::ops::DerefMut for Mirror<T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        unsafe { std::slice::from_raw_parts_mut(self.map, self.len) }
+        unsafe { std::slice::from_raw_parts_mut(self.map.as_mut_ptr() as *mut T, self.len) }
     }
 }
 
@@ -115,3 +257,63 @@ impl<T> std::borrow::BorrowMut<[T]> for Mirror<T> {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn grow_wrapped() {
+        let mut mirror = Mirror::<u32>::zeroed(1).unwrap();
+        let capacity = mirror.len() / 2;
+        mirror[capacity - 2] = 1;
+        mirror[capacity - 1] = 2;
+        mirror[0] = 3;
+        mirror[1] = 4;
+        mirror.grow(0, capacity - 2, 4).unwrap();
+
+        let new_capacity = mirror.len() / 2;
+        assert!(new_capacity > capacity);
+        assert_eq!(mirror[..4], [1, 2, 3, 4]);
+        assert_eq!(mirror[new_capacity..new_capacity + 4], [1, 2, 3, 4]);
+        assert!(mirror[4..new_capacity].iter().all(|x| *x == 0));
+    }
+
+    #[test]
+    fn drop_elements() {
+        let value = Rc::new(());
+        let mut mirror = Mirror::with_value(1, value.clone()).unwrap();
+        assert_eq!(Rc::strong_count(&value), mirror.len() / 2 + 1);
+        mirror.grow_with_value(0, 1, 2, value.clone()).unwrap();
+        assert_eq!(Rc::strong_count(&value), mirror.len() / 2 + 1);
+        drop(mirror);
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn zeroed_huge() {
+        let page_size = HugePageSize::Size2MiB;
+        let mut mirror = match Mirror::<u8>::zeroed_huge(1, page_size) {
+            Ok(mirror) => mirror,
+            // Huge pages may not be configured in the test environment
+            Err(_) => return,
+        };
+        assert_eq!(mirror.len(), 2 * page_size.size());
+        mirror[0] = 1;
+        assert_eq!(mirror[page_size.size()], 1);
+        if mirror.grow(0, 0, 1).is_ok() {
+            assert_eq!(mirror.len(), 4 * page_size.size());
+            assert_eq!(mirror[2 * page_size.size()], 1);
+        }
+    }
+
+    #[test]
+    fn lock() {
+        let mirror = Mirror::<u8>::zeroed(1).unwrap();
+        // Locking may not be allowed in the test environment
+        if mirror.lock().is_ok() {
+            mirror.unlock().unwrap();
+        }
+    }
+}
@@ -0,0 +1,401 @@
+use crate::raw::{self, page_size};
+use std::{io::Error, marker::PhantomData, ptr::NonNull};
+
+/// A growable buffer backed directly by anonymous memory.
+///
+/// Like [`Vec`], but growing the buffer remaps its pages rather than copying them where possible,
+/// which on Linux uses `mremap`.
+/// Since newly mapped memory is zeroed, [`resize_zeroed`](`Self::resize_zeroed`) only writes to
+/// memory that was previously used.
+pub struct PageBuf<T> {
+    ptr: NonNull<T>,
+    len: usize,
+    // The number of bytes mapped, which is a multiple of the page size
+    mapped: usize,
+    // Elements at or above this index have never been written, so they are zeroed
+    high_water: usize,
+    _type: PhantomData<T>,
+}
+
+// Safety: the buffer is uniquely owned, like a `Vec<T>`
+unsafe impl<T: Send> Send for PageBuf<T> {}
+unsafe impl<T: Sync> Sync for PageBuf<T> {}
+
+impl<T> PageBuf<T> {
+    /// Create an empty buffer, without mapping any memory.
+    ///
+    /// If the alignment of `T` exceeds the [page size](`crate::raw::page_size`), the buffer can't
+    /// grow, and every allocation returns [`raw::Error::Misaligned`].
+    pub fn new() -> Self {
+        Self {
+            ptr: NonNull::dangling(),
+            len: 0,
+            mapped: 0,
+            high_water: 0,
+            _type: PhantomData,
+        }
+    }
+
+    /// Create an empty buffer that can hold at least `capacity` elements without growing.
+    ///
+    /// Returns [`raw::Error::Misaligned`] if the alignment of `T` exceeds the
+    /// [page size](`crate::raw::page_size`).
+    pub fn with_capacity(capacity: usize) -> Result<Self, Error> {
+        if std::mem::align_of::<T>() > page_size() {
+            return Err(raw::Error::Misaligned.into());
+        }
+        let mut buf = Self::new();
+        buf.reserve_exact(capacity)?;
+        Ok(buf)
+    }
+
+    /// The number of elements in the buffer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the buffer contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of elements the buffer can hold without growing.
+    pub fn capacity(&self) -> usize {
+        self.mapped
+            .checked_div(std::mem::size_of::<T>())
+            .unwrap_or(usize::MAX)
+    }
+
+    /// Get a pointer to the elements.
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr.as_ptr()
+    }
+
+    /// Get a mutable pointer to the elements.
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.ptr.as_ptr()
+    }
+
+    // Resize the mapping to `mapped` bytes, which must be a multiple of the page size and hold
+    // every element.
+    fn remap(&mut self, mapped: usize) -> Result<(), Error> {
+        let old = self.ptr.as_ptr() as *mut u8;
+        // Safety: the old mapping is owned by the buffer, and the new mapping holds every element
+        let ptr = unsafe {
+            if self.mapped == 0 {
+                raw::allocate(mapped, false)?
+            } else if mapped == 0 {
+                raw::deallocate(old, self.mapped);
+                NonNull::<T>::dangling().as_ptr() as *mut u8
+            } else {
+                match raw::reallocate(old, self.mapped, mapped) {
+                    Ok(ptr) => ptr,
+                    Err(_) => {
+                        let ptr = raw::allocate(mapped, false)?;
+                        let bytes = self.len * std::mem::size_of::<T>();
+                        std::ptr::copy_nonoverlapping(old, ptr, bytes);
+                        raw::deallocate(old, self.mapped);
+                        // Only the copied elements were written to the new mapping
+                        self.high_water = self.len;
+                        ptr
+                    }
+                }
+            }
+        };
+        self.ptr = NonNull::new(ptr as *mut T).unwrap();
+        self.mapped = mapped;
+        self.high_water = self.high_water.min(self.capacity());
+        Ok(())
+    }
+
+    fn grow(&mut self, capacity: usize, exact: bool) -> Result<(), Error> {
+        if capacity <= self.capacity() {
+            return Ok(());
+        }
+        if std::mem::align_of::<T>() > page_size() {
+            return Err(raw::Error::Misaligned.into());
+        }
+        let mut bytes = capacity
+            .checked_mul(std::mem::size_of::<T>())
+            .ok_or(raw::Error::Overflow)?;
+        if !exact {
+            bytes = bytes.max(self.mapped.saturating_mul(2));
+        }
+        self.remap(raw::round_up_to_page(bytes)?)
+    }
+
+    /// Reserve capacity for at least `additional` more elements.
+    ///
+    /// The capacity grows geometrically, to amortize the cost of remapping.
+    pub fn reserve(&mut self, additional: usize) -> Result<(), Error> {
+        let capacity = self
+            .len
+            .checked_add(additional)
+            .ok_or(raw::Error::Overflow)?;
+        self.grow(capacity, false)
+    }
+
+    /// Reserve capacity for at least `additional` more elements, without over-allocating.
+    ///
+    /// The capacity is still rounded up to fill whole pages.
+    pub fn reserve_exact(&mut self, additional: usize) -> Result<(), Error> {
+        let capacity = self
+            .len
+            .checked_add(additional)
+            .ok_or(raw::Error::Overflow)?;
+        self.grow(capacity, true)
+    }
+
+    /// Shrink the capacity to the fewest pages that hold the elements.
+    pub fn shrink_to_fit(&mut self) -> Result<(), Error> {
+        let bytes = self.len * std::mem::size_of::<T>();
+        let mapped = raw::round_up_to_page(bytes)?;
+        if mapped < self.mapped {
+            self.remap(mapped)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Append an element to the buffer.
+    pub fn push(&mut self, value: T) -> Result<(), Error> {
+        if self.len == self.capacity() {
+            self.reserve(1)?;
+        }
+        // Safety: the slot is within the capacity
+        unsafe { self.as_mut_ptr().add(self.len).write(value) };
+        self.len += 1;
+        self.high_water = self.high_water.max(self.len);
+        Ok(())
+    }
+
+    /// Remove the last element and return it, or `None` if the buffer is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        // Safety: the element is initialized, and no longer reachable
+        Some(unsafe { self.as_ptr().add(self.len).read() })
+    }
+
+    /// Shorten the buffer to `len` elements, dropping the rest.
+    ///
+    /// Has no effect if the buffer is already shorter.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let tail: *mut [T] = &mut self[len..];
+        // Elements are leaked rather than dropped twice if dropping panics
+        self.len = len;
+        // Safety: the elements are initialized, and no longer reachable
+        unsafe { std::ptr::drop_in_place(tail) };
+    }
+
+    /// Remove all elements.
+    pub fn clear(&mut self) {
+        self.truncate(0)
+    }
+}
+
+impl<T: Clone> PageBuf<T> {
+    /// Append clones of the elements of a slice.
+    pub fn extend_from_slice(&mut self, values: &[T]) -> Result<(), Error> {
+        self.reserve(values.len())?;
+        for value in values {
+            // Safety: the slot is within the reserved capacity
+            unsafe { self.as_mut_ptr().add(self.len).write(value.clone()) };
+            self.len += 1;
+            self.high_water = self.high_water.max(self.len);
+        }
+        Ok(())
+    }
+}
+
+impl<T: crate::ZeroInit> PageBuf<T> {
+    /// Resize the buffer to `len` elements, filling new elements with zeros.
+    ///
+    /// New elements that have never been written are already zeroed, so growing the buffer only
+    /// writes to memory that was used before being truncated.
+    pub fn resize_zeroed(&mut self, len: usize) -> Result<(), Error> {
+        if len <= self.len {
+            self.truncate(len);
+            return Ok(());
+        }
+        self.reserve(len - self.len)?;
+        let written = self.high_water.min(len);
+        if written > self.len {
+            // Safety: the elements are within the capacity
+            unsafe {
+                std::ptr::write_bytes(self.as_mut_ptr().add(self.len), 0, written - self.len)
+            };
+        }
+        self.len = len;
+        self.high_water = self.high_water.max(len);
+        Ok(())
+    }
+}
+
+impl<T> Default for PageBuf<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for PageBuf<T> {
+    fn drop(&mut self) {
+        self.clear();
+        if self.mapped != 0 {
+            // Safety: the mapping is owned by the buffer
+            unsafe { raw::deallocate(self.ptr.as_ptr() as *mut u8, self.mapped) };
+        }
+    }
+}
+
+impl<T> std::ops::Deref for PageBuf<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        // Safety: elements before the length are initialized
+        unsafe { std::slice::from_raw_parts(self.as_ptr(), self.len) }
+    }
+}
+
+impl<T> std::ops::DerefMut for PageBuf<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: elements before the length are initialized
+        unsafe { std::slice::from_raw_parts_mut(self.as_mut_ptr(), self.len) }
+    }
+}
+
+impl<T> AsRef<[T]> for PageBuf<T> {
+    fn as_ref(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T> AsMut<[T]> for PageBuf<T> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for PageBuf<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn push_pages() {
+        let count = 3 * page_size() / std::mem::size_of::<u32>() + 10;
+        let mut buf = PageBuf::new();
+        for i in 0..count {
+            buf.push(i as u32).unwrap();
+        }
+        assert_eq!(buf.len(), count);
+        assert!(buf.capacity() >= count);
+        assert!(buf.iter().copied().eq(0..count as u32));
+        assert_eq!(buf.pop(), Some(count as u32 - 1));
+        buf.shrink_to_fit().unwrap();
+        assert!(buf.iter().copied().eq(0..count as u32 - 1));
+    }
+
+    #[test]
+    fn regrow_zeroed() {
+        let per_page = page_size() / std::mem::size_of::<u64>();
+        let mut buf = PageBuf::<u64>::new();
+        buf.resize_zeroed(4 * per_page).unwrap();
+        assert!(buf.iter().all(|x| *x == 0));
+        buf.iter_mut().for_each(|x| *x = u64::MAX);
+
+        // The tail of the last page is retained, and the rest is unmapped
+        buf.truncate(per_page + 1);
+        buf.shrink_to_fit().unwrap();
+        assert_eq!(buf.capacity(), 2 * per_page);
+
+        buf.resize_zeroed(8 * per_page).unwrap();
+        assert!(buf[..per_page + 1].iter().all(|x| *x == u64::MAX));
+        assert!(buf[per_page + 1..].iter().all(|x| *x == 0));
+
+        // Shrinking without unmapping still zeroes the regrown tail
+        buf.iter_mut().for_each(|x| *x = 1);
+        buf.resize_zeroed(1).unwrap();
+        buf.resize_zeroed(8 * per_page).unwrap();
+        assert_eq!(buf[0], 1);
+        assert!(buf[1..].iter().all(|x| *x == 0));
+
+        buf.clear();
+        buf.shrink_to_fit().unwrap();
+        assert_eq!(buf.capacity(), 0);
+        buf.resize_zeroed(per_page).unwrap();
+        assert!(buf.iter().all(|x| *x == 0));
+    }
+
+    #[test]
+    fn zero_sized() {
+        let mut buf = PageBuf::new();
+        assert_eq!(buf.capacity(), usize::MAX);
+        for _ in 0..1000 {
+            buf.push(()).unwrap();
+        }
+        buf.extend_from_slice(&[(); 10]).unwrap();
+        assert_eq!(buf.len(), 1010);
+        buf.truncate(5);
+        assert_eq!(buf.pop(), Some(()));
+        assert_eq!(buf.len(), 4);
+        buf.shrink_to_fit().unwrap();
+    }
+
+    #[test]
+    fn drop_elements() {
+        let counter = Arc::new(());
+        let mut buf = PageBuf::new();
+        for _ in 0..1000 {
+            buf.push(counter.clone()).unwrap();
+        }
+        buf.extend_from_slice(&[counter.clone(), counter.clone()])
+            .unwrap();
+        assert_eq!(Arc::strong_count(&counter), 1003);
+        buf.truncate(500);
+        assert_eq!(Arc::strong_count(&counter), 501);
+        drop(buf.pop());
+        assert_eq!(Arc::strong_count(&counter), 500);
+        buf.shrink_to_fit().unwrap();
+        buf.reserve(10_000).unwrap();
+        assert_eq!(Arc::strong_count(&counter), 500);
+        drop(buf);
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn over_aligned() {
+        #[repr(align(65536))]
+        struct Aligned(u8);
+
+        if page_size() < 65536 {
+            let misaligned = |err: Error| {
+                let err = err
+                    .get_ref()
+                    .and_then(|err| err.downcast_ref::<raw::Error>());
+                matches!(err, Some(raw::Error::Misaligned))
+            };
+            assert!(misaligned(
+                PageBuf::<Aligned>::with_capacity(1).err().unwrap()
+            ));
+            let mut buf = PageBuf::new();
+            assert!(misaligned(buf.push(Aligned(0)).unwrap_err()));
+            assert!(buf.is_empty());
+        } else {
+            let mut buf = PageBuf::new();
+            buf.push(Aligned(1)).unwrap();
+            assert_eq!(buf[0].0, 1);
+        }
+    }
+}
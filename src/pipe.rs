@@ -0,0 +1,171 @@
+use crate::{raw, Consumer, Mirror, MirrorRing, Producer};
+use std::io::{BufRead, Error, ErrorKind, Read, Write};
+
+/// A byte pipe over a mirrored memory region.
+///
+/// The pipe is non-blocking: writing to a full pipe or reading from an empty pipe fails with
+/// [`ErrorKind::WouldBlock`].
+/// Since the buffer is mirrored, [`PipeReader::fill_buf`] always returns every readable byte as a
+/// single slice.
+pub struct MirrorPipe {
+    ring: MirrorRing<u8>,
+}
+
+impl MirrorPipe {
+    /// Create a pipe.
+    ///
+    /// The resulting pipe can hold at least `min_capacity` bytes.
+    pub fn new(min_capacity: usize) -> Result<Self, Error> {
+        let min_size = min_capacity.checked_mul(2).ok_or(raw::Error::Overflow)?;
+        Ok(Self {
+            ring: MirrorRing::new(Mirror::zeroed(min_size)?),
+        })
+    }
+
+    /// The maximum number of bytes the pipe can hold.
+    pub fn capacity(&self) -> usize {
+        self.ring.capacity()
+    }
+
+    /// Split the pipe into its writing and reading halves.
+    pub fn split(self) -> (PipeWriter, PipeReader) {
+        let (producer, consumer) = self.ring.split();
+        (PipeWriter { producer }, PipeReader { consumer })
+    }
+}
+
+/// The writing half of a [`MirrorPipe`].
+pub struct PipeWriter {
+    producer: Producer<u8>,
+}
+
+impl PipeWriter {
+    /// The number of bytes waiting to be read.
+    pub fn len(&self) -> usize {
+        self.producer.len()
+    }
+
+    /// Returns `true` if there are no bytes waiting to be read.
+    pub fn is_empty(&self) -> bool {
+        self.producer.is_empty()
+    }
+}
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.producer.is_abandoned() {
+            return Err(ErrorKind::BrokenPipe.into());
+        }
+        let window = self.producer.write_window();
+        if window.is_empty() {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        let count = window.len().min(buf.len());
+        window[..count].copy_from_slice(&buf[..count]);
+        self.producer.commit(count);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// The reading half of a [`MirrorPipe`].
+///
+/// Reading returns end-of-file once the writer has been dropped and every byte has been read.
+pub struct PipeReader {
+    consumer: Consumer<u8>,
+}
+
+impl PipeReader {
+    /// The number of bytes available to read.
+    pub fn len(&self) -> usize {
+        self.consumer.len()
+    }
+
+    /// Returns `true` if there are no bytes available to read.
+    pub fn is_empty(&self) -> bool {
+        self.consumer.is_empty()
+    }
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let window = self.fill_buf()?;
+        let count = window.len().min(buf.len());
+        buf[..count].copy_from_slice(&window[..count]);
+        self.consume(count);
+        Ok(count)
+    }
+}
+
+impl BufRead for PipeReader {
+    fn fill_buf(&mut self) -> Result<&[u8], Error> {
+        // Check for the writer first, so no bytes written before it was dropped are missed
+        let abandoned = self.consumer.is_abandoned();
+        let window = self.consumer.read_window();
+        if window.is_empty() && !abandoned {
+            Err(ErrorKind::WouldBlock.into())
+        } else {
+            Ok(window)
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.consumer.consume(amt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overflow() {
+        assert!(MirrorPipe::new(usize::MAX).is_err());
+    }
+
+    #[test]
+    fn would_block() {
+        let (mut writer, mut reader) = MirrorPipe::new(1).unwrap().split();
+        let mut buf = [0; 4];
+        assert_eq!(
+            reader.read(&mut buf).unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        );
+        while writer.write(&[1; 64]).is_ok() {}
+        assert_eq!(
+            writer.write(&[1]).unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        );
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert_eq!(writer.write(&[1; 64]).unwrap(), 4);
+    }
+
+    #[test]
+    fn end_of_file() {
+        let (mut writer, mut reader) = MirrorPipe::new(1).unwrap().split();
+        writer.write_all(b"hello").unwrap();
+        drop(writer);
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"hello");
+        assert_eq!(reader.read(&mut [0; 4]).unwrap(), 0);
+    }
+
+    #[test]
+    fn broken_pipe() {
+        let (mut writer, reader) = MirrorPipe::new(1).unwrap().split();
+        writer.write_all(b"hello").unwrap();
+        drop(reader);
+        assert_eq!(
+            writer.write(b"world").unwrap_err().kind(),
+            ErrorKind::BrokenPipe
+        );
+        assert_eq!(writer.write(b"").unwrap(), 0);
+    }
+}
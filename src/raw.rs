@@ -4,45 +4,143 @@
 #[cfg_attr(windows, path = "raw/windows.rs")]
 mod map_impl;
 
+mod error;
+pub use error::*;
+
 mod view;
 pub use view::*;
 
-use std::io::Error;
+mod mapping;
+pub use mapping::*;
+
+mod options;
+pub use options::*;
+
+mod reservation;
+pub use reservation::*;
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use once_cell::race::OnceNonZeroUsize;
+
+/// The size of a page of virtual memory.
+pub fn page_size() -> usize {
+    static PAGE_SIZE: OnceNonZeroUsize = OnceNonZeroUsize::new();
+    PAGE_SIZE.get_or_init(map_impl::page_size).get()
+}
+
+// Round a length up to a multiple of the page size.
+pub(crate) fn round_up_to_page(len: usize) -> Result<usize, Error> {
+    let page_size = page_size();
+    len.checked_add(page_size - 1)
+        .map(|len| len / page_size * page_size)
+        .ok_or(Error::Overflow)
+}
+
+// Round a value up to a multiple of the alignment, which must be a power of two.
+pub(crate) fn round_up(value: u64, alignment: u64) -> Result<u64, Error> {
+    debug_assert!(alignment.is_power_of_two());
+    value
+        .checked_add(alignment - 1)
+        .map(|value| value & !(alignment - 1))
+        .ok_or(Error::Overflow)
+}
+
+// A unique identity for a new object, which unlike a handle is never reused.
+pub(crate) fn next_object_id() -> u64 {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+// Returns `true` if the value is a multiple of the alignment, which must be a power of two.
+pub(crate) fn is_aligned(value: u64, alignment: u64) -> bool {
+    debug_assert!(alignment.is_power_of_two());
+    value & (alignment - 1) == 0
+}
+
+/// Flush the instruction cache for a region of memory.
+///
+/// This must be called after writing instructions to memory, before executing them.
+/// On some architectures, such as x86, this has no effect.
+///
+/// # Safety
+/// The region must be mapped.
+pub unsafe fn flush_instruction_cache(ptr: *const u8, len: usize) {
+    map_impl::flush_instruction_cache(ptr, len)
+}
 
 /// Map a view of an object to memory.
 ///
-/// Returns a tuple containing the memory map and the size of the map.
-pub fn map(view: &View<'_>) -> Result<(*const u8, usize), Error> {
-    map_impl::map(view)
+/// This is equivalent to mapping with the default [`MapOptions`].
+pub fn map<'a>(view: &View<'a>) -> Result<Mapping<'a>, Error> {
+    MapOptions::new().map(view)
 }
 
 /// Map a mutable view of an object to memory.
 ///
-/// Returns a tuple containing the memory map and the size of the map.
-pub fn map_mut(view: &ViewMut<'_>) -> Result<(*mut u8, usize), Error> {
-    map_impl::map_mut(view)
+/// This is equivalent to mapping with the default [`MapOptions`].
+///
+/// # Safety
+/// See [`MapOptions::map_mut`].
+pub unsafe fn map_mut<'a>(view: &ViewMut<'a>) -> Result<MappingMut<'a>, Error> {
+    MapOptions::new().map_mut(view)
 }
 
 /// Map views of objects contiguously to memory.
 ///
-/// Returns a tuple containing the memory map and the size of the map.
-pub fn map_multiple(views: &[View<'_>]) -> Result<(*const u8, usize), Error> {
-    map_impl::map_multiple(views)
+/// This is equivalent to mapping with the default [`MapOptions`].
+pub fn map_multiple<'a>(views: &[View<'a>]) -> Result<Mapping<'a>, Error> {
+    MapOptions::new().map_multiple(views)
 }
 
 /// Map mutable views of objects contiguously to memory.
 ///
-/// Returns a tuple containing the memory map and the size of the map.
-pub fn map_multiple_mut(views: &[ViewMut<'_>]) -> Result<(*mut u8, usize), Error> {
-    map_impl::map_multiple_mut(views)
+/// This is equivalent to mapping with the default [`MapOptions`].
+///
+/// # Safety
+/// See [`MapOptions::map_multiple_mut`].
+pub unsafe fn map_multiple_mut<'a>(views: &[ViewMut<'a>]) -> Result<MappingMut<'a>, Error> {
+    MapOptions::new().map_multiple_mut(views)
 }
 
-/// Unmap a memory map.
+/// Lock all memory mapped by the process into RAM.
 ///
-/// # Safety
-/// * `ptr` must be a memory map allocated with one of [`map`], [`map_mut`], [`map_multiple`], or
-/// [`map_multiple_mut`].
-/// * `view_lengths` must produce the lengths of each view in the memory map.
-pub unsafe fn unmap(ptr: *mut u8, view_lengths: impl Iterator<Item = usize>) {
-    map_impl::unmap(ptr, view_lengths)
+/// If `current` is set, all currently mapped memory is locked.
+/// If `future` is set, all memory mapped in the future is locked.
+/// Returns [`Error::LockLimitExceeded`] if the limit on locked memory is exceeded,
+/// [`Error::LockNotPermitted`] if the process is not allowed to lock memory, or
+/// [`Error::Unsupported`] on Windows.
+pub fn lock_all(current: bool, future: bool) -> Result<(), Error> {
+    map_impl::lock_all(current, future)
+}
+
+/// Unlock all memory mapped by the process.
+///
+/// Returns [`Error::Unsupported`] on Windows.
+pub fn unlock_all() -> Result<(), Error> {
+    map_impl::unlock_all()
+}
+
+// Private anonymous memory for allocators, which must not allocate themselves.
+// Sparse memory doesn't reserve swap space, where supported.
+pub(crate) fn allocate(len: usize, sparse: bool) -> Result<*mut u8, Error> {
+    map_impl::allocate(len, sparse)
+}
+
+// Resizes an allocation, possibly moving it, or returns an error if it can't be resized without
+// copying.
+pub(crate) unsafe fn reallocate(
+    ptr: *mut u8,
+    old_len: usize,
+    new_len: usize,
+) -> Result<*mut u8, Error> {
+    map_impl::reallocate(ptr, old_len, new_len)
+}
+
+// Releases the pages of an allocation, which are zeroed when next accessed.
+pub(crate) unsafe fn discard(ptr: *mut u8, len: usize) -> Result<(), Error> {
+    map_impl::discard(ptr, len)
+}
+
+pub(crate) unsafe fn deallocate(ptr: *mut u8, len: usize) {
+    map_impl::deallocate(ptr, len)
 }
@@ -0,0 +1,148 @@
+//! Errors produced when mapping memory.
+
+use super::map_impl;
+
+/// An error produced when mapping memory.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The requested permissions are not allowed by the object.
+    PermissionDenied,
+    /// An offset or length is not a multiple of the required granularity.
+    Misaligned,
+    /// A length is zero.
+    ZeroLength,
+    /// An offset or length is too large to be represented.
+    Overflow,
+    /// A view or range extends past the end of its object or mapping.
+    OutOfBounds {
+        /// The end of the view or range, in bytes.
+        end: u64,
+        /// The size of the object or mapping, in bytes.
+        size: u64,
+    },
+    /// An object can't be shrunk.
+    WouldTruncate,
+    /// A view doesn't match the view it replaces.
+    IncompatibleView,
+    /// There is not enough address space or memory available.
+    AddressSpaceExhausted,
+    /// The object is not sealed against being shrunk and written.
+    NotSealed,
+    /// The requested address is not available.
+    AddressUnavailable,
+    /// Huge pages of the requested size are not configured, or not enough are free.
+    HugePagesUnavailable,
+    /// The name of a named object is not valid.
+    InvalidName,
+    /// The combination of options is not valid.
+    InvalidOptions {
+        /// The reason the options are not valid.
+        reason: &'static str,
+    },
+    /// The limit on memory locked into RAM has been exceeded.
+    LockLimitExceeded,
+    /// The process is not allowed to lock memory into RAM.
+    LockNotPermitted,
+    /// The operation is not supported on this platform.
+    Unsupported,
+    /// A system call failed.
+    Os {
+        /// The name of the system call.
+        syscall: &'static str,
+        /// The error code reported by the operating system.
+        code: i32,
+    },
+}
+
+impl Error {
+    pub(crate) fn from_os_error(syscall: &'static str, error: std::io::Error) -> Self {
+        let code = error.raw_os_error().unwrap_or(0);
+        if map_impl::is_out_of_memory(syscall, code) {
+            Self::AddressSpaceExhausted
+        } else {
+            Self::Os { syscall, code }
+        }
+    }
+
+    pub(crate) fn last_os_error(syscall: &'static str) -> Self {
+        Self::from_os_error(syscall, std::io::Error::last_os_error())
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::PermissionDenied => write!(f, "permissions are not allowed by the object"),
+            Self::Misaligned => write!(f, "offset or length is not a multiple of the granularity"),
+            Self::ZeroLength => write!(f, "length must not be zero"),
+            Self::Overflow => write!(f, "offset or length is too large"),
+            Self::OutOfBounds { end, size } => write!(
+                f,
+                "range ends at byte {}, past the end of the region ({} bytes)",
+                end, size
+            ),
+            Self::WouldTruncate => write!(f, "object can't be shrunk"),
+            Self::IncompatibleView => write!(f, "view doesn't match the mapped view"),
+            Self::AddressSpaceExhausted => write!(f, "not enough address space or memory"),
+            Self::NotSealed => write!(f, "object is not sealed against modification"),
+            Self::AddressUnavailable => write!(f, "requested address is not available"),
+            Self::HugePagesUnavailable => write!(f, "huge pages are not available"),
+            Self::InvalidName => write!(f, "invalid object name"),
+            Self::InvalidOptions { reason } => write!(f, "invalid options: {}", reason),
+            Self::LockLimitExceeded => write!(f, "limit on locked memory exceeded"),
+            Self::LockNotPermitted => write!(f, "not allowed to lock memory"),
+            Self::Unsupported => write!(f, "operation not supported on this platform"),
+            Self::Os { syscall, code } => write!(
+                f,
+                "{} failed: {}",
+                syscall,
+                std::io::Error::from_raw_os_error(*code)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for std::io::Error {
+    fn from(error: Error) -> Self {
+        use std::io::ErrorKind;
+        // The error is kept as the payload, so OS errors retain the name of the system call
+        let kind = match error {
+            Error::PermissionDenied | Error::NotSealed | Error::LockNotPermitted => {
+                ErrorKind::PermissionDenied
+            }
+            Error::Misaligned
+            | Error::ZeroLength
+            | Error::Overflow
+            | Error::OutOfBounds { .. }
+            | Error::WouldTruncate
+            | Error::IncompatibleView
+            | Error::InvalidName
+            | Error::InvalidOptions { .. } => ErrorKind::InvalidInput,
+            Error::AddressSpaceExhausted
+            | Error::AddressUnavailable
+            | Error::HugePagesUnavailable
+            | Error::LockLimitExceeded
+            | Error::Unsupported => ErrorKind::Other,
+            Error::Os { code, .. } => Self::from_raw_os_error(code).kind(),
+        };
+        Self::new(kind, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn into_io_error() {
+        let error = std::io::Error::from(Error::Os {
+            syscall: "mmap",
+            code: 1,
+        });
+        assert_eq!(error.kind(), std::io::Error::from_raw_os_error(1).kind());
+        assert!(error.to_string().starts_with("mmap failed"));
+    }
+}
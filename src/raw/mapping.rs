@@ -0,0 +1,767 @@
+//! Owned memory maps.
+
+use super::{is_aligned, map_impl, page_size, round_up_to_page, Error, Object, View, ViewMut};
+use std::{marker::PhantomData, ops::Range};
+
+/// Memory protection of a mapped region.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Protection {
+    /// the region is inaccessible
+    None,
+    /// the region is read-only
+    Read,
+    /// the region is readable and writable
+    ReadWrite,
+    /// the region is readable and executable
+    ReadExecute,
+}
+
+/// Advice about the expected use of a mapped region.
+///
+/// Not all advice is supported on all platforms.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Advice {
+    /// no special treatment
+    Normal,
+    /// expect accesses in sequential order
+    Sequential,
+    /// expect accesses in random order
+    Random,
+    /// expect accesses in the near future
+    WillNeed,
+    /// do not expect accesses in the near future, and release the pages
+    DontNeed,
+    /// the contents of the pages are no longer needed, and may be freed lazily
+    Free,
+    /// back the region with transparent huge pages
+    HugePage,
+    /// do not back the region with transparent huge pages
+    NoHugePage,
+    /// do not make the region available to child processes
+    DontFork,
+    /// make the region available to child processes
+    DoFork,
+    /// exclude the region from core dumps
+    DontDump,
+    /// include the region in core dumps
+    DoDump,
+}
+
+/// The layout of a single view within a memory map.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct ViewLayout {
+    pub(crate) length: usize,
+    pub(crate) offset: u64,
+    /// Identifies the mapped object.
+    pub(crate) object: u64,
+    /// The view may be made writable.
+    pub(crate) write: bool,
+    /// The view may be made executable.
+    pub(crate) execute: bool,
+    pub(crate) copy_on_write: bool,
+}
+
+impl<'a> From<&View<'a>> for ViewLayout {
+    fn from(view: &View<'a>) -> Self {
+        Self {
+            length: view.length.into(),
+            offset: view.offset.into(),
+            object: view.object.id(),
+            write: false,
+            execute: view.permissions.execute,
+            copy_on_write: false,
+        }
+    }
+}
+
+impl<'a> From<&ViewMut<'a>> for ViewLayout {
+    fn from(view: &ViewMut<'a>) -> Self {
+        Self {
+            length: view.length.into(),
+            offset: view.offset.into(),
+            object: view.object.id(),
+            write: view.copy_on_write || view.permissions.write,
+            execute: view.permissions.execute,
+            copy_on_write: view.copy_on_write,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RawMapping {
+    ptr: *mut u8,
+    len: usize,
+    // The huge page size, if mapped with huge pages, or the page size
+    page_size: usize,
+    views: Box<[ViewLayout]>,
+}
+
+// Safety: the memory map is uniquely owned, like a `Box<[u8]>`
+unsafe impl Send for RawMapping {}
+unsafe impl Sync for RawMapping {}
+
+impl RawMapping {
+    fn new(ptr: *mut u8, page_size: usize, views: Box<[ViewLayout]>) -> Self {
+        Self {
+            ptr,
+            len: views.iter().map(|v| v.length).sum(),
+            page_size,
+            views,
+        }
+    }
+
+    fn view_lengths(&self) -> impl ExactSizeIterator<Item = usize> + '_ {
+        self.views.iter().map(|v| v.length)
+    }
+
+    // The view must match the single mapped view, other than its length.
+    fn check_remap(&self, layout: &ViewLayout) -> Result<(), Error> {
+        match &*self.views {
+            [view]
+                if ViewLayout {
+                    length: view.length,
+                    ..*layout
+                } == *view =>
+            {
+                Ok(())
+            }
+            _ => Err(Error::IncompatibleView),
+        }
+    }
+
+    fn set_remapped(&mut self, ptr: *mut u8, layout: ViewLayout) {
+        self.ptr = ptr;
+        self.len = layout.length;
+        self.views = Box::new([layout]);
+    }
+
+    fn check_range(&self, range: &Range<usize>) -> Result<(), Error> {
+        if range.start > range.end || range.end > self.len {
+            Err(Error::OutOfBounds {
+                end: range.end as u64,
+                size: self.len as u64,
+            })
+        } else if !is_aligned(range.start as u64, self.page_size as u64)
+            || !is_aligned(range.end as u64, self.page_size as u64)
+        {
+            Err(Error::Misaligned)
+        } else {
+            Ok(())
+        }
+    }
+
+    // The portion of each view within the range, as a pointer and length.
+    fn segments(
+        &self,
+        range: Range<usize>,
+    ) -> impl Iterator<Item = (*mut u8, usize, &ViewLayout)> + '_ {
+        let ptr = self.ptr;
+        self.views
+            .iter()
+            .scan(0, |offset, view| {
+                let start = *offset;
+                *offset += view.length;
+                Some((start, view))
+            })
+            .filter_map(move |(start, view)| {
+                let begin = range.start.max(start);
+                let end = range.end.min(start + view.length);
+                if begin < end {
+                    // Safety: the segment is within the memory map
+                    Some((unsafe { ptr.add(begin) }, end - begin, view))
+                } else {
+                    None
+                }
+            })
+    }
+
+    unsafe fn protect(&self, range: Range<usize>, protection: Protection) -> Result<(), Error> {
+        self.check_range(&range)?;
+        let allowed = self
+            .segments(range.clone())
+            .all(|(_, _, view)| match protection {
+                Protection::None | Protection::Read => true,
+                Protection::ReadWrite => view.write,
+                Protection::ReadExecute => view.execute,
+            });
+        if !allowed {
+            return Err(Error::PermissionDenied);
+        }
+        for (ptr, len, view) in self.segments(range) {
+            map_impl::protect(ptr, len, protection, view.copy_on_write)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&self, range: Range<usize>, asynchronous: bool) -> Result<(), Error> {
+        if range.start > range.end || range.end > self.len {
+            return Err(Error::OutOfBounds {
+                end: range.end as u64,
+                size: self.len as u64,
+            });
+        }
+        // The memory map always ends on a page boundary
+        let start = range.start / page_size() * page_size();
+        let end = round_up_to_page(range.end)?;
+        for (ptr, len, _) in self.segments(start..end) {
+            // Safety: the segment is within the memory map
+            unsafe { map_impl::flush(ptr, len, asynchronous)? };
+        }
+        Ok(())
+    }
+
+    fn lock(&self, range: Range<usize>) -> Result<(), Error> {
+        self.check_range(&range)?;
+        for (ptr, len, _) in self.segments(range) {
+            // Safety: the segment is within the memory map
+            unsafe { map_impl::lock(ptr, len)? };
+        }
+        Ok(())
+    }
+
+    fn unlock(&self, range: Range<usize>) -> Result<(), Error> {
+        self.check_range(&range)?;
+        for (ptr, len, _) in self.segments(range) {
+            // Safety: the segment is within the memory map
+            unsafe { map_impl::unlock(ptr, len)? };
+        }
+        Ok(())
+    }
+
+    fn advise(&self, range: Range<usize>, advice: Advice) -> Result<(), Error> {
+        self.check_range(&range)?;
+        // Safety: the range is within the memory map
+        unsafe { map_impl::advise(self.ptr.add(range.start), range.len(), advice) }
+    }
+}
+
+impl Drop for RawMapping {
+    fn drop(&mut self) {
+        // Safety: the pointer and view lengths describe a memory map created by `map_impl`
+        unsafe { map_impl::unmap(self.ptr, self.view_lengths()) }
+    }
+}
+
+/// A memory map of one or more views.
+///
+/// The memory is unmapped when the mapping is dropped.
+#[derive(Debug)]
+pub struct Mapping<'a> {
+    raw: RawMapping,
+    object: PhantomData<&'a Object>,
+}
+
+impl<'a> Mapping<'a> {
+    pub(crate) fn new(ptr: *const u8, page_size: usize, views: Box<[ViewLayout]>) -> Self {
+        Self {
+            raw: RawMapping::new(ptr as *mut u8, page_size, views),
+            object: PhantomData,
+        }
+    }
+
+    /// Returns a pointer to the beginning of the memory map.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.raw.ptr
+    }
+
+    /// Returns the length of each view in the memory map.
+    pub fn view_lengths(&self) -> impl ExactSizeIterator<Item = usize> + '_ {
+        self.raw.view_lengths()
+    }
+
+    /// Change the protection of a range of the memory map.
+    ///
+    /// The range must be aligned to the [page size](`super::page_size`), or the huge page size if
+    /// the memory map uses huge pages.
+    /// Returns [`Error::PermissionDenied`] if the protection is not allowed by the mapped objects.
+    /// Read-only mappings can never be made writable.
+    ///
+    /// # Safety
+    /// The memory must not be accessed in a way that is not allowed by the new protection,
+    /// including through this mapping's `Deref` implementation.
+    pub unsafe fn protect(
+        &mut self,
+        range: Range<usize>,
+        protection: Protection,
+    ) -> Result<(), Error> {
+        self.raw.protect(range, protection)
+    }
+
+    /// Advise the operating system about the expected use of a range of the memory map.
+    ///
+    /// The range must be aligned to the [page size](`super::page_size`), or the huge page size if
+    /// the memory map uses huge pages.
+    /// Returns [`Error::Unsupported`] if the advice is not supported on this platform.
+    pub fn advise(&self, range: Range<usize>, advice: Advice) -> Result<(), Error> {
+        self.raw.advise(range, advice)
+    }
+
+    /// Lock a range of the memory map into RAM, preventing it from being swapped out.
+    ///
+    /// The range must be aligned to the [page size](`super::page_size`), or the huge page size if
+    /// the memory map uses huge pages.
+    /// Returns [`Error::LockLimitExceeded`] if the limit on locked memory is exceeded, or
+    /// [`Error::LockNotPermitted`] if the process is not allowed to lock memory.
+    pub fn lock(&self, range: Range<usize>) -> Result<(), Error> {
+        self.raw.lock(range)
+    }
+
+    /// Unlock a range of the memory map, allowing it to be swapped out.
+    ///
+    /// The range must be aligned to the [page size](`super::page_size`), or the huge page size if
+    /// the memory map uses huge pages.
+    pub fn unlock(&self, range: Range<usize>) -> Result<(), Error> {
+        self.raw.unlock(range)
+    }
+
+    /// Remap the memory map to a view with a different length, such as after the object grows.
+    ///
+    /// The memory map must consist of a single view, and `view` must be of the same object, with
+    /// the same offset and permissions, or this returns [`Error::IncompatibleView`].
+    /// The memory map may move to a different address, and changes to its protection and locking
+    /// may not be preserved.
+    /// If remapping fails, the original memory map is returned with the error.
+    pub fn remap<'b>(mut self, view: &View<'b>) -> Result<Mapping<'b>, (Self, Error)> {
+        let layout = ViewLayout::from(view);
+        if let Err(err) = self.raw.check_remap(&layout) {
+            return Err((self, err));
+        }
+        // Safety: the view is of the mapped object
+        let ptr = match unsafe { map_impl::remap(self.raw.ptr, self.raw.len, view) } {
+            Ok(ptr) => ptr,
+            Err(err) => return Err((self, err)),
+        };
+        self.raw.set_remapped(ptr as *mut u8, layout);
+        Ok(Mapping {
+            raw: self.raw,
+            object: PhantomData,
+        })
+    }
+
+    /// Release the borrow of the mapped objects.
+    ///
+    /// A memory map remains valid after its objects are dropped, so the mapping may outlive them.
+    ///
+    /// # Safety
+    /// Without the borrow, the objects may be modified through methods that require exclusive
+    /// access, such as [`Object::seal`].
+    /// The detached mapping must not be used in a way that those methods rely on being prevented,
+    /// such as writing to an object after it is sealed against writes.
+    pub unsafe fn detach(self) -> Mapping<'static> {
+        Mapping {
+            raw: self.raw,
+            object: PhantomData,
+        }
+    }
+}
+
+impl<'a> std::ops::Deref for Mapping<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { std::slice::from_raw_parts(self.raw.ptr, self.raw.len) }
+    }
+}
+
+impl<'a> AsRef<[u8]> for Mapping<'a> {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+/// A mutable memory map of one or more views.
+///
+/// The memory is unmapped when the mapping is dropped.
+#[derive(Debug)]
+pub struct MappingMut<'a> {
+    raw: RawMapping,
+    // Handles of the distinct mapped objects, for flushing
+    handles: Box<[map_impl::Handle]>,
+    object: PhantomData<&'a Object>,
+}
+
+impl<'a> MappingMut<'a> {
+    // Handles must be obtained before mapping, so a failure doesn't leak the memory map.
+    pub(crate) fn handles(views: &[ViewMut<'a>]) -> Result<Box<[map_impl::Handle]>, Error> {
+        let mut objects: Vec<&map_impl::Object> = Vec::new();
+        for view in views {
+            if objects.iter().all(|object| object.id() != view.object.id()) {
+                objects.push(view.object);
+            }
+        }
+        objects.into_iter().map(map_impl::Object::handle).collect()
+    }
+
+    pub(crate) fn new(
+        ptr: *mut u8,
+        page_size: usize,
+        views: Box<[ViewLayout]>,
+        handles: Box<[map_impl::Handle]>,
+    ) -> Self {
+        Self {
+            raw: RawMapping::new(ptr, page_size, views),
+            handles,
+            object: PhantomData,
+        }
+    }
+
+    /// Returns a pointer to the beginning of the memory map.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.raw.ptr
+    }
+
+    /// Returns a mutable pointer to the beginning of the memory map.
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.raw.ptr
+    }
+
+    /// Returns the length of each view in the memory map.
+    pub fn view_lengths(&self) -> impl ExactSizeIterator<Item = usize> + '_ {
+        self.raw.view_lengths()
+    }
+
+    /// Change the protection of a range of the memory map.
+    ///
+    /// The range must be aligned to the [page size](`super::page_size`), or the huge page size if
+    /// the memory map uses huge pages.
+    /// Returns [`Error::PermissionDenied`] if the protection is not allowed by the mapped objects.
+    ///
+    /// # Safety
+    /// The memory must not be accessed in a way that is not allowed by the new protection,
+    /// including through this mapping's `Deref` and `DerefMut` implementations.
+    pub unsafe fn protect(
+        &mut self,
+        range: Range<usize>,
+        protection: Protection,
+    ) -> Result<(), Error> {
+        self.raw.protect(range, protection)
+    }
+
+    /// Advise the operating system about the expected use of a range of the memory map.
+    ///
+    /// The range must be aligned to the [page size](`super::page_size`), or the huge page size if
+    /// the memory map uses huge pages.
+    /// Returns [`Error::Unsupported`] if the advice is not supported on this platform.
+    ///
+    /// Copy-on-write pages released with [`Advice::DontNeed`] or [`Advice::Free`] lose their
+    /// modifications.
+    pub fn advise(&mut self, range: Range<usize>, advice: Advice) -> Result<(), Error> {
+        self.raw.advise(range, advice)
+    }
+
+    /// Lock a range of the memory map into RAM, preventing it from being swapped out.
+    ///
+    /// The range must be aligned to the [page size](`super::page_size`), or the huge page size if
+    /// the memory map uses huge pages.
+    /// Returns [`Error::LockLimitExceeded`] if the limit on locked memory is exceeded, or
+    /// [`Error::LockNotPermitted`] if the process is not allowed to lock memory.
+    pub fn lock(&self, range: Range<usize>) -> Result<(), Error> {
+        self.raw.lock(range)
+    }
+
+    /// Unlock a range of the memory map, allowing it to be swapped out.
+    ///
+    /// The range must be aligned to the [page size](`super::page_size`), or the huge page size if
+    /// the memory map uses huge pages.
+    pub fn unlock(&self, range: Range<usize>) -> Result<(), Error> {
+        self.raw.unlock(range)
+    }
+
+    /// Write modifications in a range of the memory map to the backing objects, and wait for the
+    /// writes to complete.
+    ///
+    /// The range is expanded to include every page it touches.
+    /// If `metadata` is `true`, file metadata is also written, like [`Object::sync_all`].
+    /// On Windows, file metadata is always written.
+    pub fn flush(&self, range: Range<usize>, metadata: bool) -> Result<(), Error> {
+        self.raw.flush(range, false)?;
+        for handle in self.handles.iter() {
+            handle.sync(metadata)?;
+        }
+        Ok(())
+    }
+
+    /// Begin writing modifications in a range of the memory map to the backing objects, without
+    /// waiting for the writes to complete.
+    ///
+    /// The range is expanded to include every page it touches.
+    pub fn flush_async(&self, range: Range<usize>) -> Result<(), Error> {
+        self.raw.flush(range, true)
+    }
+
+    /// Remap the memory map to a view with a different length, such as after the object grows.
+    ///
+    /// The memory map must consist of a single view, and `view` must be of the same object, with
+    /// the same offset and permissions, or this returns [`Error::IncompatibleView`].
+    /// The memory map may move to a different address, and changes to its protection and locking
+    /// may not be preserved.
+    /// If remapping fails, the original memory map is returned with the error.
+    ///
+    /// # Safety
+    /// The remapped view is subject to the same requirements as
+    /// [`MapOptions::map_mut`](`super::MapOptions::map_mut`).
+    pub unsafe fn remap<'b>(mut self, view: &ViewMut<'b>) -> Result<MappingMut<'b>, (Self, Error)> {
+        let layout = ViewLayout::from(view);
+        if let Err(err) = self.raw.check_remap(&layout) {
+            return Err((self, err));
+        }
+        // The view is of the mapped object
+        let ptr = match map_impl::remap_mut(self.raw.ptr, self.raw.len, view) {
+            Ok(ptr) => ptr,
+            Err(err) => return Err((self, err)),
+        };
+        self.raw.set_remapped(ptr, layout);
+        Ok(MappingMut {
+            raw: self.raw,
+            handles: self.handles,
+            object: PhantomData,
+        })
+    }
+
+    /// Release the borrow of the mapped objects.
+    ///
+    /// A memory map remains valid after its objects are dropped, so the mapping may outlive them.
+    ///
+    /// # Safety
+    /// Without the borrow, the objects may be modified through methods that require exclusive
+    /// access, such as [`Object::seal`].
+    /// The detached mapping must not be used in a way that those methods rely on being prevented,
+    /// such as writing to an object after it is sealed against writes.
+    pub unsafe fn detach(self) -> MappingMut<'static> {
+        MappingMut {
+            raw: self.raw,
+            handles: self.handles,
+            object: PhantomData,
+        }
+    }
+}
+
+impl<'a> std::ops::Deref for MappingMut<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { std::slice::from_raw_parts(self.raw.ptr, self.raw.len) }
+    }
+}
+
+impl<'a> std::ops::DerefMut for MappingMut<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { std::slice::from_raw_parts_mut(self.raw.ptr, self.raw.len) }
+    }
+}
+
+impl<'a> AsRef<[u8]> for MappingMut<'a> {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<'a> AsMut<[u8]> for MappingMut<'a> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use std::io::Read;
+
+    #[test]
+    fn flush_file() {
+        let path = std::env::temp_dir().join(format!("memory-magic-flush-{}", std::process::id()));
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        file.set_len(page_size() as u64).unwrap();
+
+        // Safety: the file is not accessible to other processes
+        let object = unsafe { FileOptions::new(&file) }
+            .write(true)
+            .finish()
+            .unwrap();
+        let length = Length::exact(page_size()).unwrap();
+        let view = object
+            .view_mut(Offset::exact(0).unwrap(), length, WritePermissions::Write)
+            .unwrap();
+        // Safety: the object is only mapped once
+        let mut mapping = unsafe { map_mut(&view) }.unwrap();
+        mapping[1..4].copy_from_slice(b"abc");
+        mapping.flush(1..4, true).unwrap();
+        mapping.flush_async(0..page_size()).unwrap();
+        assert!(mapping.flush(0..page_size() + 1, false).is_err());
+
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).unwrap();
+        assert_eq!(&contents[..4], b"\0abc");
+    }
+
+    #[test]
+    fn grow_and_remap() {
+        let page = page_size();
+        let offset = Offset::exact(0).unwrap();
+        let object = Object::anonymous(page, ReadPermissions::Read).unwrap();
+        let view = object
+            .view_mut(
+                offset,
+                Length::exact(page).unwrap(),
+                WritePermissions::Write,
+            )
+            .unwrap();
+        // Safety: the object is only mutably mapped once
+        let mut mapping = unsafe { map_mut(&view) }.unwrap();
+        let reader = map(&object
+            .view(offset, Length::exact(page).unwrap(), ReadPermissions::Read)
+            .unwrap())
+        .unwrap();
+        mapping[page - 1] = 1;
+
+        // The object grows while it's mapped
+        object.set_len(3 * page as u64).unwrap();
+        assert_eq!(object.size(), 3 * page as u64);
+        assert!(matches!(
+            object.set_len(page as u64),
+            Err(Error::WouldTruncate)
+        ));
+        let length = Length::exact(3 * page).unwrap();
+        let view = object
+            .view_mut(offset, length, WritePermissions::Write)
+            .unwrap();
+        // Safety: the object is only mutably mapped once
+        let mut mapping = unsafe { mapping.remap(&view) }.unwrap();
+        assert_eq!(mapping.len(), 3 * page);
+        assert_eq!(mapping[page - 1], 1);
+        assert_eq!(mapping[page], 0);
+        mapping[3 * page - 1] = 2;
+
+        let view = object.view(offset, length, ReadPermissions::Read).unwrap();
+        let reader = reader.remap(&view).unwrap();
+        assert_eq!(reader[page - 1], 1);
+        assert_eq!(reader[3 * page - 1], 2);
+    }
+
+    #[test]
+    fn remap_other_object() {
+        let length = Length::exact(page_size()).unwrap();
+        let offset = Offset::exact(0).unwrap();
+        let a = Object::anonymous(page_size(), ReadPermissions::Read).unwrap();
+        // Safety: the object isn't used after it's dropped
+        let mapping = unsafe {
+            map(&a.view(offset, length, ReadPermissions::Read).unwrap())
+                .unwrap()
+                .detach()
+        };
+        drop(a);
+
+        // The new object may reuse the dropped object's file descriptor
+        let b = Object::anonymous(2 * page_size(), ReadPermissions::Read).unwrap();
+        let view = b
+            .view(
+                offset,
+                Length::exact(2 * page_size()).unwrap(),
+                ReadPermissions::Read,
+            )
+            .unwrap();
+        let (mapping, err) = mapping.remap(&view).unwrap_err();
+        assert!(matches!(err, Error::IncompatibleView));
+        // The original mapping is still usable
+        assert_eq!(mapping.len(), page_size());
+        assert_eq!(mapping[0], 0);
+
+        let mapping = map(&b.view(offset, length, ReadPermissions::Read).unwrap()).unwrap();
+        let mapping = mapping.remap(&view).unwrap();
+        assert_eq!(mapping.len(), 2 * page_size());
+    }
+
+    #[test]
+    fn protect() {
+        let page = page_size();
+        let length = Length::exact(2 * page).unwrap();
+        let offset = Offset::exact(0).unwrap();
+        let object = Object::anonymous(2 * page, ReadPermissions::Read).unwrap();
+
+        let view = object.view(offset, length, ReadPermissions::Read).unwrap();
+        let mut mapping = map(&view).unwrap();
+        // Safety: the mapping isn't accessed while inaccessible
+        unsafe {
+            assert!(matches!(
+                mapping.protect(0..page, Protection::ReadWrite),
+                Err(Error::PermissionDenied)
+            ));
+            assert!(matches!(
+                mapping.protect(0..page, Protection::ReadExecute),
+                Err(Error::PermissionDenied)
+            ));
+            assert!(matches!(
+                mapping.protect(0..1, Protection::Read),
+                Err(Error::Misaligned)
+            ));
+            assert!(matches!(
+                mapping.protect(page..3 * page, Protection::Read),
+                Err(Error::OutOfBounds { .. })
+            ));
+            #[allow(clippy::reversed_empty_ranges)]
+            let reversed = page..0;
+            assert!(matches!(
+                mapping.protect(reversed, Protection::Read),
+                Err(Error::OutOfBounds { .. })
+            ));
+            mapping.protect(page..2 * page, Protection::None).unwrap();
+            mapping.protect(page..2 * page, Protection::Read).unwrap();
+        }
+        assert_eq!(mapping[2 * page - 1], 0);
+
+        let view = object
+            .view_mut(offset, length, WritePermissions::Write)
+            .unwrap();
+        // Safety: the object is only mutably mapped once
+        let mut mapping = unsafe { map_mut(&view) }.unwrap();
+        // Safety: the mapping is only written while writable
+        unsafe {
+            mapping.protect(0..2 * page, Protection::Read).unwrap();
+            mapping.protect(0..page, Protection::ReadWrite).unwrap();
+        }
+        mapping[0] = 1;
+    }
+
+    #[test]
+    fn advise() {
+        let page = page_size();
+        let length = Length::exact(2 * page).unwrap();
+        let offset = Offset::exact(0).unwrap();
+        let object = Object::anonymous(2 * page, ReadPermissions::Read).unwrap();
+        let view = object
+            .view_mut(offset, length, WritePermissions::Write)
+            .unwrap();
+        // Safety: the object is only mutably mapped once
+        let mut mapping = unsafe { map_mut(&view) }.unwrap();
+        mapping[0] = 1;
+        mapping.advise(0..2 * page, Advice::Sequential).unwrap();
+        mapping.advise(0..page, Advice::DontNeed).unwrap();
+        // Shared pages are reloaded from the object
+        assert_eq!(mapping[0], 1);
+        assert!(matches!(
+            mapping.advise(1..page, Advice::Normal),
+            Err(Error::Misaligned)
+        ));
+        assert!(matches!(
+            mapping.advise(0..3 * page, Advice::Normal),
+            Err(Error::OutOfBounds { .. })
+        ));
+
+        let view = object
+            .view_mut(offset, length, WritePermissions::CopyOnWrite)
+            .unwrap();
+        // Safety: the copy isn't shared
+        let mut copy = unsafe { map_mut(&view) }.unwrap();
+        copy[0] = 2;
+        copy.advise(0..page, Advice::DontNeed).unwrap();
+        // Private modifications are discarded
+        assert_eq!(copy[0], 1);
+    }
+}
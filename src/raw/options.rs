@@ -0,0 +1,241 @@
+//! Options for mapping views to memory.
+
+use super::{
+    is_aligned, map_impl, mapping::ViewLayout, Error, Length, Mapping, MappingMut, Offset, View,
+    ViewMut,
+};
+
+/// The size of huge pages.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum HugePageSize {
+    /// 2 MiB pages.
+    Size2MiB,
+    /// 1 GiB pages.
+    Size1GiB,
+}
+
+impl HugePageSize {
+    /// The size of a page, in bytes.
+    pub fn size(self) -> usize {
+        1 << self.shift()
+    }
+
+    pub(crate) fn shift(self) -> u32 {
+        match self {
+            Self::Size2MiB => 21,
+            Self::Size1GiB => 30,
+        }
+    }
+}
+
+/// Options for mapping views to memory.
+#[derive(Clone, Debug, Default)]
+pub struct MapOptions {
+    pub(crate) lock: bool,
+    pub(crate) populate: bool,
+    pub(crate) no_reserve: bool,
+    pub(crate) huge_pages: Option<HugePageSize>,
+    pub(crate) address_hint: usize,
+    pub(crate) fixed_noreplace: bool,
+    pub(crate) stack: bool,
+}
+
+impl MapOptions {
+    /// Create a new set of options for mapping views.
+    ///
+    /// All options are initially set to `false`, with no huge pages and no address hint.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lock the mapped memory into RAM, preventing it from being swapped out.
+    ///
+    /// If the limit on locked memory is exceeded, mapping fails with
+    /// [`Error::LockLimitExceeded`].
+    pub fn lock(&mut self, lock: bool) -> &mut Self {
+        self.lock = lock;
+        self
+    }
+
+    /// Fault in the mapped memory up front, rather than on first access.
+    ///
+    /// On platforms without direct support, this only advises that the memory will be needed.
+    pub fn populate(&mut self, populate: bool) -> &mut Self {
+        self.populate = populate;
+        self
+    }
+
+    /// Don't reserve swap space for the mapped memory.
+    ///
+    /// This only has an effect on Linux.
+    pub fn no_reserve(&mut self, no_reserve: bool) -> &mut Self {
+        self.no_reserve = no_reserve;
+        self
+    }
+
+    /// Map the memory with huge pages of the specified size.
+    ///
+    /// The object must be backed by huge pages, and the offset and length of each view must be
+    /// multiples of the huge page size.
+    /// Views of objects created with [`Object::anonymous_huge`](`super::Object::anonymous_huge`)
+    /// are always mapped with huge pages.
+    /// Huge pages are only supported on Linux.
+    pub fn huge_pages(&mut self, size: Option<HugePageSize>) -> &mut Self {
+        self.huge_pages = size;
+        self
+    }
+
+    /// Prefer mapping the memory at the specified address.
+    ///
+    /// The address must be a multiple of [`Length::granularity`], or the huge page size if huge
+    /// pages are used.
+    /// If the address is not available, the memory is mapped elsewhere, unless
+    /// [`fixed_noreplace`](`Self::fixed_noreplace`) is set.
+    /// A null pointer removes the hint.
+    pub fn address_hint(&mut self, address: *const u8) -> &mut Self {
+        self.address_hint = address as usize;
+        self
+    }
+
+    /// Require the memory to be mapped at the [address hint](`Self::address_hint`).
+    ///
+    /// If any memory is already mapped at that address, mapping fails with
+    /// [`Error::AddressUnavailable`] rather than replacing it.
+    pub fn fixed_noreplace(&mut self, fixed_noreplace: bool) -> &mut Self {
+        self.fixed_noreplace = fixed_noreplace;
+        self
+    }
+
+    /// Indicate that the memory is used as a thread stack.
+    ///
+    /// This only has an effect on Linux.
+    pub fn stack(&mut self, stack: bool) -> &mut Self {
+        self.stack = stack;
+        self
+    }
+
+    /// The alignment required of the address and views.
+    pub(crate) fn alignment(&self) -> usize {
+        self.huge_pages
+            .map_or_else(Length::granularity, HugePageSize::size)
+    }
+
+    // The page size of the mapped memory, which may be smaller than the alignment on Windows.
+    fn page_size(&self) -> usize {
+        self.huge_pages
+            .map_or_else(super::page_size, HugePageSize::size)
+    }
+
+    // Returns the options used for mapping the views.
+    pub(crate) fn validate(
+        &self,
+        views: &[(Offset, Length, Option<HugePageSize>)],
+    ) -> Result<Self, Error> {
+        if self.fixed_noreplace && self.address_hint == 0 {
+            return Err(Error::InvalidOptions {
+                reason: "`fixed_noreplace` requires an address hint",
+            });
+        }
+
+        // Views of objects backed by huge pages must be mapped with huge pages
+        let mut options = self.clone();
+        if options.huge_pages.is_none() {
+            options.huge_pages = views.iter().find_map(|view| view.2);
+        }
+        if views
+            .iter()
+            .any(|view| view.2.is_some() && view.2 != options.huge_pages)
+        {
+            return Err(Error::InvalidOptions {
+                reason: "huge page size does not match the object",
+            });
+        }
+        map_impl::check_options(&options)?;
+
+        let alignment = options.alignment();
+        if !is_aligned(options.address_hint as u64, alignment as u64) {
+            return Err(Error::Misaligned);
+        }
+        if options.huge_pages.is_some() {
+            for (offset, length, _) in views {
+                if !is_aligned((*offset).into(), alignment as u64)
+                    || !is_aligned(usize::from(*length) as u64, alignment as u64)
+                {
+                    return Err(Error::Misaligned);
+                }
+            }
+        }
+        Ok(options)
+    }
+
+    /// Map a view of an object to memory with the specified options.
+    pub fn map<'a>(&self, view: &View<'a>) -> Result<Mapping<'a>, Error> {
+        let options = self.validate(&[(view.offset, view.length, view.huge_pages)])?;
+        let (ptr, _) = map_impl::map(view, &options)?;
+        Ok(Mapping::new(
+            ptr,
+            options.page_size(),
+            Box::new([view.into()]),
+        ))
+    }
+
+    /// Map a mutable view of an object to memory with the specified options.
+    ///
+    /// # Safety
+    /// While the mapping exists, the mapped range of the object must not be accessed through any
+    /// other mapping, except through raw pointers that are never used while a reference obtained
+    /// from this mapping is live.
+    pub unsafe fn map_mut<'a>(&self, view: &ViewMut<'a>) -> Result<MappingMut<'a>, Error> {
+        let options = self.validate(&[(view.offset, view.length, view.huge_pages)])?;
+        let handles = MappingMut::handles(std::slice::from_ref(view))?;
+        let (ptr, _) = map_impl::map_mut(view, &options)?;
+        Ok(MappingMut::new(
+            ptr,
+            options.page_size(),
+            Box::new([view.into()]),
+            handles,
+        ))
+    }
+
+    /// Map views of objects contiguously to memory with the specified options.
+    pub fn map_multiple<'a>(&self, views: &[View<'a>]) -> Result<Mapping<'a>, Error> {
+        let layout = views
+            .iter()
+            .map(|view| (view.offset, view.length, view.huge_pages))
+            .collect::<Vec<_>>();
+        let options = self.validate(&layout)?;
+        let (ptr, _) = map_impl::map_multiple(views, &options)?;
+        Ok(Mapping::new(
+            ptr,
+            options.page_size(),
+            views.iter().map(ViewLayout::from).collect(),
+        ))
+    }
+
+    /// Map mutable views of objects contiguously to memory with the specified options.
+    ///
+    /// # Safety
+    /// While the mapping exists, the mapped ranges of the objects must not be accessed through any
+    /// other mapping, except through raw pointers that are never used while a reference obtained
+    /// from this mapping is live.
+    /// If the views overlap, the mapping itself must only be accessed through raw pointers.
+    pub unsafe fn map_multiple_mut<'a>(
+        &self,
+        views: &[ViewMut<'a>],
+    ) -> Result<MappingMut<'a>, Error> {
+        let layout = views
+            .iter()
+            .map(|view| (view.offset, view.length, view.huge_pages))
+            .collect::<Vec<_>>();
+        let options = self.validate(&layout)?;
+        let handles = MappingMut::handles(views)?;
+        let (ptr, _) = map_impl::map_multiple_mut(views, &options)?;
+        Ok(MappingMut::new(
+            ptr,
+            options.page_size(),
+            views.iter().map(ViewLayout::from).collect(),
+            handles,
+        ))
+    }
+}
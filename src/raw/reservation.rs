@@ -0,0 +1,237 @@
+//! Reserved regions of address space.
+
+use super::{
+    is_aligned, map_impl, page_size, Error, HugePageSize, Length, MapOptions, Offset, View, ViewMut,
+};
+use std::ops::Range;
+
+/// A reserved region of virtual address space.
+///
+/// Reserved memory is inaccessible until pages are committed, or views are mapped into it.
+/// The region never moves, so pointers into it remain valid as it is filled.
+/// Everything within the region is released when the reservation is dropped.
+#[derive(Debug)]
+pub struct Reservation {
+    ptr: *mut u8,
+    len: usize,
+}
+
+// Safety: the reservation is uniquely owned, and only exposes raw pointers
+unsafe impl Send for Reservation {}
+unsafe impl Sync for Reservation {}
+
+impl Reservation {
+    /// Reserve a region of address space.
+    pub fn new(length: Length) -> Result<Self, Error> {
+        let len = length.into();
+        Ok(Self {
+            ptr: map_impl::reserve_address_space(len)?,
+            len,
+        })
+    }
+
+    /// Get a pointer to the start of the region.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.ptr
+    }
+
+    /// Get a mutable pointer to the start of the region.
+    pub fn as_mut_ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+
+    /// The length of the region, in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the region is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn check_range(&self, range: &Range<usize>) -> Result<(), Error> {
+        if range.start > range.end || range.end > self.len {
+            Err(Error::OutOfBounds {
+                end: range.end as u64,
+                size: self.len as u64,
+            })
+        } else if !is_aligned(range.start as u64, page_size() as u64)
+            || !is_aligned(range.end as u64, page_size() as u64)
+        {
+            Err(Error::Misaligned)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Commit anonymous memory to the range, making it readable and writable.
+    ///
+    /// Newly committed pages are zeroed, and pages that are already committed are unchanged.
+    /// The range must be aligned to the [page size](`super::page_size`).
+    ///
+    /// # Safety
+    /// The range must not contain mapped views, or memory that is referenced.
+    pub unsafe fn commit(&self, range: Range<usize>) -> Result<(), Error> {
+        self.check_range(&range)?;
+        if range.is_empty() {
+            return Ok(());
+        }
+        map_impl::commit(self.ptr.add(range.start), range.len())
+    }
+
+    /// Release the memory in the range, making it inaccessible again.
+    ///
+    /// On Unix, this also unmaps any views within the range.
+    /// The range must be aligned to the [page size](`super::page_size`).
+    ///
+    /// # Safety
+    /// The memory in the range must not be referenced.
+    pub unsafe fn decommit(&self, range: Range<usize>) -> Result<(), Error> {
+        self.check_range(&range)?;
+        if range.is_empty() {
+            return Ok(());
+        }
+        map_impl::decommit(self.ptr.add(range.start), range.len())
+    }
+
+    // Returns the address of the view, and the options used to map it.
+    fn check_view(
+        &self,
+        offset: usize,
+        view: (Offset, Length, Option<HugePageSize>),
+    ) -> Result<(*mut u8, MapOptions), Error> {
+        let options = MapOptions::new().validate(&[view])?;
+        let end = offset.checked_add(view.1.into()).ok_or(Error::Overflow)?;
+        self.check_range(&(offset..end))?;
+        if !is_aligned(offset as u64, options.alignment() as u64) {
+            return Err(Error::Misaligned);
+        }
+        // Safety: the offset is within the reservation
+        Ok((unsafe { self.ptr.add(offset) }, options))
+    }
+
+    /// Map a view into the region at `offset`, replacing any memory in its place.
+    ///
+    /// The offset must be a multiple of [`Length::granularity`], or the huge page size if the
+    /// object is backed by huge pages.
+    /// The view remains mapped until it is [decommitted](`Self::decommit`) or the reservation is
+    /// dropped, even if the object is dropped first.
+    /// Returns [`Error::Unsupported`] on Windows.
+    ///
+    /// # Safety
+    /// The memory replaced by the view must not be referenced.
+    pub unsafe fn map(&self, offset: usize, view: &View<'_>) -> Result<*const u8, Error> {
+        let (ptr, options) =
+            self.check_view(offset, (view.offset, view.length, view.huge_pages))?;
+        map_impl::map_fixed(ptr, view, &options)?;
+        Ok(ptr)
+    }
+
+    /// Map a mutable view into the region at `offset`, replacing any memory in its place.
+    ///
+    /// See [`map`](`Self::map`).
+    ///
+    /// # Safety
+    /// The memory replaced by the view must not be referenced.
+    pub unsafe fn map_mut(&self, offset: usize, view: &ViewMut<'_>) -> Result<*mut u8, Error> {
+        let (ptr, options) =
+            self.check_view(offset, (view.offset, view.length, view.huge_pages))?;
+        map_impl::map_fixed_mut(ptr, view, &options)?;
+        Ok(ptr)
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        // Safety: the region is owned by the reservation
+        unsafe { map_impl::release(self.ptr, self.len) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit() {
+        let page = page_size();
+        let reservation = Reservation::new(Length::exact(4 * page).unwrap()).unwrap();
+        let ptr = reservation.as_mut_ptr();
+        // Safety: the memory is only accessed while committed
+        unsafe {
+            reservation.commit(page..3 * page).unwrap();
+            ptr.add(page).write(1);
+            ptr.add(3 * page - 1).write(2);
+
+            // Committing again leaves the contents unchanged
+            reservation.commit(0..2 * page).unwrap();
+            assert_eq!(ptr.add(page).read(), 1);
+            assert_eq!(ptr.read(), 0);
+
+            reservation.decommit(page..3 * page).unwrap();
+            reservation.commit(page..3 * page).unwrap();
+            assert_eq!(ptr.add(page).read(), 0);
+            assert_eq!(ptr.add(3 * page - 1).read(), 0);
+
+            reservation.commit(page..page).unwrap();
+            assert!(matches!(
+                reservation.commit(0..5 * page),
+                Err(Error::OutOfBounds { .. })
+            ));
+            assert!(matches!(
+                reservation.commit(1..page),
+                Err(Error::Misaligned)
+            ));
+            assert!(matches!(
+                reservation.decommit(3 * page..5 * page),
+                Err(Error::OutOfBounds { .. })
+            ));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn map_mut() {
+        use crate::raw::{map, Object, ReadPermissions, WritePermissions};
+
+        let page = page_size();
+        let reservation = Reservation::new(Length::exact(4 * page).unwrap()).unwrap();
+        let object = Object::anonymous(2 * page, ReadPermissions::Read).unwrap();
+        let length = Length::exact(page).unwrap();
+        let view = object
+            .view_mut(
+                Offset::exact(page as u64).unwrap(),
+                length,
+                WritePermissions::Write,
+            )
+            .unwrap();
+        // Safety: the replaced memory isn't referenced, and the object is only mapped here
+        unsafe {
+            let ptr = reservation.map_mut(2 * page, &view).unwrap();
+            assert_eq!(ptr, reservation.as_mut_ptr().add(2 * page));
+            ptr.write(1);
+            assert_eq!(reservation.as_ptr().add(2 * page).read(), 1);
+
+            // The view is of the second page of the object
+            let whole = object
+                .view(
+                    Offset::exact(0).unwrap(),
+                    Length::exact(2 * page).unwrap(),
+                    ReadPermissions::Read,
+                )
+                .unwrap();
+            let mapping = map(&whole).unwrap();
+            assert_eq!(mapping[page], 1);
+
+            assert!(matches!(
+                reservation.map_mut(4 * page, &view),
+                Err(Error::OutOfBounds { .. })
+            ));
+            assert!(matches!(
+                reservation.map_mut(1, &view),
+                Err(Error::Misaligned)
+            ));
+        }
+    }
+}
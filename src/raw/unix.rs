@@ -1,19 +1,46 @@
-use super::view::{Length, Offset, View, ViewMut};
-use std::{convert::TryInto, io::Error, num::NonZeroUsize};
+use super::{
+    next_object_id,
+    view::{Length, Offset, Seals, View, ViewMut},
+    Advice, Error, HugePageSize, MapOptions, Protection,
+};
+use std::{convert::TryInto, num::NonZeroUsize};
 
-fn access_denied() -> Error {
-    Error::from_raw_os_error(libc::EACCES)
+// Other system calls also report ENOMEM for unrelated reasons, such as exceeding the limit on
+// locked memory
+pub fn is_out_of_memory(syscall: &str, code: i32) -> bool {
+    matches!(syscall, "mmap" | "mremap") && code == libc::ENOMEM
+}
+
+pub fn file_size(file: &std::fs::File) -> Result<u64, Error> {
+    file.metadata()
+        .map(|metadata| metadata.len())
+        .map_err(|err| Error::from_os_error("fstat", err))
+}
+
+fn shm_name(name: &str) -> Result<std::ffi::CString, Error> {
+    std::ffi::CString::new(name).map_err(|_| Error::InvalidName)
+}
+
+// Variadic on Apple platforms, so the mode must be promoted.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+unsafe fn shm_open(name: &std::ffi::CStr, oflag: libc::c_int, mode: u32) -> libc::c_int {
+    libc::shm_open(name.as_ptr(), oflag, mode as libc::c_uint)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+unsafe fn shm_open(name: &std::ffi::CStr, oflag: libc::c_int, mode: u32) -> libc::c_int {
+    libc::shm_open(name.as_ptr(), oflag, mode as libc::mode_t)
 }
 
 fn open_anonymous(size: i64) -> Result<libc::c_int, Error> {
     let fd = shm_open_anonymous::shm_open_anonymous();
     if fd == -1 {
-        Err(Error::last_os_error())
+        Err(Error::last_os_error("shm_open"))
     } else {
         // Safety: fd is valid
         unsafe {
             if libc::ftruncate(fd, size) != 0 {
-                let err = Error::last_os_error();
+                let err = Error::last_os_error("ftruncate");
                 libc::close(fd);
                 Err(err)
             } else {
@@ -83,10 +110,43 @@ impl<'a> ViewImpl for ViewMut<'a> {
     }
 }
 
+// Part of a view, used for extending a mapping in place.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+struct Tail<'v, T> {
+    view: &'v T,
+    offset: Offset,
+    length: Length,
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+impl<'v, T: ViewImpl> ViewImpl for Tail<'v, T> {
+    fn offset(&self) -> Offset {
+        self.offset
+    }
+
+    fn length(&self) -> Length {
+        self.length
+    }
+
+    fn prot_flags(&self) -> libc::c_int {
+        self.view.prot_flags()
+    }
+
+    fn map_flags(&self) -> libc::c_int {
+        self.view.map_flags()
+    }
+
+    fn object(&self) -> &Object {
+        self.view.object()
+    }
+}
+
+// Unlike file mapping objects on Windows, file descriptors don't restrict execute access, which
+// is only checked when creating views.
 #[derive(Debug)]
 pub struct Object {
     fd: libc::c_int,
-    execute: bool,
+    id: u64,
 }
 
 impl Drop for Object {
@@ -99,139 +159,980 @@ impl Drop for Object {
 }
 
 impl Object {
-    pub fn anonymous(size: usize, execute: bool) -> Result<Self, Error> {
-        Ok(Object {
-            fd: open_anonymous(size.try_into().unwrap())?,
-            execute,
+    // Takes ownership of the file descriptor.
+    fn new(fd: libc::c_int) -> Self {
+        Object {
+            fd,
+            id: next_object_id(),
+        }
+    }
+
+    pub fn anonymous(size: usize, _execute: bool) -> Result<Self, Error> {
+        Ok(Object::new(open_anonymous(size.try_into().unwrap())?))
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn anonymous_huge(
+        size: usize,
+        page_size: HugePageSize,
+        _execute: bool,
+    ) -> Result<Self, Error> {
+        // The size is already a multiple of the huge page size
+        const MFD_HUGE_SHIFT: u32 = 26;
+        let page_size_log2 = page_size.shift();
+
+        // The kernel rejects huge page sizes that it doesn't support
+        let flags = libc::MFD_CLOEXEC | libc::MFD_HUGETLB | page_size_log2 << MFD_HUGE_SHIFT;
+        // Safety: the name is a valid C string
+        let fd = unsafe { libc::memfd_create(b"memory-magic\0".as_ptr() as *const _, flags) };
+        if fd == -1 {
+            let err = std::io::Error::last_os_error();
+            return Err(match err.raw_os_error() {
+                Some(libc::EINVAL) => Error::HugePagesUnavailable,
+                _ => Error::from_os_error("memfd_create", err),
+            });
+        }
+        let object = Object::new(fd);
+        // Safety: fd is valid
+        if unsafe { libc::ftruncate(fd, size.try_into().unwrap()) } != 0 {
+            return Err(Error::last_os_error("ftruncate"));
+        }
+
+        // Huge pages are reserved for shared mappings when first mapped, and remain reserved
+        // for the object, so mapping the entire object once checks that the pages are available.
+        // Safety: the pointer is selected by the kernel
+        unsafe {
+            let ptr = libc::mmap(
+                core::ptr::null_mut(),
+                size,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                fd,
+                0,
+            );
+            if ptr == libc::MAP_FAILED {
+                let err = std::io::Error::last_os_error();
+                return Err(match err.raw_os_error() {
+                    Some(libc::ENOMEM) => Error::HugePagesUnavailable,
+                    _ => Error::from_os_error("mmap", err),
+                });
+            }
+            libc::munmap(ptr, size);
+        }
+        Ok(object)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn anonymous_huge(
+        _size: usize,
+        _page_size: HugePageSize,
+        _execute: bool,
+    ) -> Result<Self, Error> {
+        Err(Error::Unsupported)
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn anonymous_sealed(size: usize, _execute: bool) -> Result<Self, Error> {
+        let flags = libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING;
+        // Safety: the name is a valid C string
+        let fd = unsafe { libc::memfd_create(b"memory-magic\0".as_ptr() as *const _, flags) };
+        if fd == -1 {
+            return Err(Error::last_os_error("memfd_create"));
+        }
+        let object = Object::new(fd);
+        // Safety: fd is valid
+        if unsafe { libc::ftruncate(fd, size.try_into().unwrap()) } != 0 {
+            return Err(Error::last_os_error("ftruncate"));
+        }
+        Ok(object)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn anonymous_sealed(_size: usize, _execute: bool) -> Result<Self, Error> {
+        Err(Error::Unsupported)
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn add_seals(&self, seals: Seals) -> Result<(), Error> {
+        let mut flags = 0;
+        if seals.shrink {
+            flags |= libc::F_SEAL_SHRINK;
+        }
+        if seals.grow {
+            flags |= libc::F_SEAL_GROW;
+        }
+        if seals.write {
+            flags |= libc::F_SEAL_WRITE;
+        }
+        if seals.future_write {
+            flags |= libc::F_SEAL_FUTURE_WRITE;
+        }
+        if seals.seal {
+            flags |= libc::F_SEAL_SEAL;
+        }
+        // Safety: fd is valid
+        if unsafe { libc::fcntl(self.fd, libc::F_ADD_SEALS, flags) } == 0 {
+            Ok(())
+        } else {
+            Err(Error::last_os_error("fcntl"))
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn add_seals(&self, _seals: Seals) -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn seals(&self) -> Result<Seals, Error> {
+        // Safety: fd is valid
+        let flags = unsafe { libc::fcntl(self.fd, libc::F_GET_SEALS) };
+        if flags == -1 {
+            let err = std::io::Error::last_os_error();
+            // Objects that don't support sealing have no seals
+            return match err.raw_os_error() {
+                Some(libc::EINVAL) => Ok(Seals::default()),
+                _ => Err(Error::from_os_error("fcntl", err)),
+            };
+        }
+        Ok(Seals {
+            shrink: flags & libc::F_SEAL_SHRINK != 0,
+            grow: flags & libc::F_SEAL_GROW != 0,
+            write: flags & libc::F_SEAL_WRITE != 0,
+            future_write: flags & libc::F_SEAL_FUTURE_WRITE != 0,
+            seal: flags & libc::F_SEAL_SEAL != 0,
         })
     }
 
+    // Objects that don't support sealing have no seals
+    #[cfg(not(target_os = "linux"))]
+    pub fn seals(&self) -> Result<Seals, Error> {
+        Ok(Seals::default())
+    }
+
+    pub fn create_named(name: &str, size: usize, _execute: bool, mode: u32) -> Result<Self, Error> {
+        let name = shm_name(name)?;
+        // Safety: the name is a valid C string
+        let fd = unsafe {
+            shm_open(
+                &name,
+                libc::O_RDWR | libc::O_CREAT | libc::O_EXCL | libc::O_CLOEXEC,
+                mode,
+            )
+        };
+        if fd == -1 {
+            return Err(Error::last_os_error("shm_open"));
+        }
+        let object = Object::new(fd);
+        // Safety: fd is valid
+        unsafe {
+            if libc::ftruncate(fd, size.try_into().unwrap()) != 0 {
+                let err = Error::last_os_error("ftruncate");
+                libc::shm_unlink(name.as_ptr());
+                return Err(err);
+            }
+        }
+        Ok(object)
+    }
+
+    pub fn open_named(name: &str, write: bool, _execute: bool) -> Result<(Self, u64), Error> {
+        let name = shm_name(name)?;
+        let access = if write { libc::O_RDWR } else { libc::O_RDONLY };
+        // Safety: the name is a valid C string
+        let fd = unsafe { shm_open(&name, access | libc::O_CLOEXEC, 0) };
+        if fd == -1 {
+            return Err(Error::last_os_error("shm_open"));
+        }
+        let object = Object::new(fd);
+        let size = object.size()?;
+        Ok((object, size))
+    }
+
+    pub fn unlink_named(name: &str) -> Result<(), Error> {
+        let name = shm_name(name)?;
+        // Safety: the name is a valid C string
+        if unsafe { libc::shm_unlink(name.as_ptr()) } == 0 {
+            Ok(())
+        } else {
+            Err(Error::last_os_error("shm_unlink"))
+        }
+    }
+
     pub unsafe fn with_file(
         file: &std::fs::File,
         _size: u64,
         write: bool,
-        execute: bool,
+        _execute: bool,
     ) -> Result<Self, Error> {
-        let file = file.try_clone()?;
-        let mapped = Object {
-            fd: std::os::unix::io::IntoRawFd::into_raw_fd(file),
-            execute,
-        };
+        let file = file
+            .try_clone()
+            .map_err(|err| Error::from_os_error("fcntl", err))?;
+        let mapped = Object::new(std::os::unix::io::IntoRawFd::into_raw_fd(file));
+        mapped.check_permissions(write)?;
+        Ok(mapped)
+    }
 
+    pub unsafe fn from_raw_fd(fd: std::os::unix::io::RawFd) -> Self {
+        Object::new(fd)
+    }
+
+    pub fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.fd
+    }
+
+    pub fn into_raw_fd(self) -> std::os::unix::io::RawFd {
+        let fd = self.fd;
+        core::mem::forget(self);
+        fd
+    }
+
+    pub fn check_permissions(&self, write: bool) -> Result<(), Error> {
         // Check permissions for the "write" permission:
         // * The file must be opened read-write
         // * We cannot write to a file opened in append-mode with mmap
-        let oflags = libc::fcntl(mapped.fd, libc::F_GETFL);
+        // Safety: fd is valid
+        let oflags = unsafe { libc::fcntl(self.fd, libc::F_GETFL) };
         if oflags == -1 {
-            return Err(Error::last_os_error());
+            return Err(Error::last_os_error("fcntl"));
         }
+        let access = oflags & libc::O_ACCMODE;
         let opened_correctly = if write {
-            oflags == libc::O_RDONLY || oflags & libc::O_RDWR != 0
+            access == libc::O_RDWR && oflags & libc::O_APPEND == 0
         } else {
-            oflags & libc::O_RDWR != 0 && oflags & libc::O_APPEND == 0
+            access == libc::O_RDONLY || access == libc::O_RDWR
         };
         if opened_correctly {
-            Ok(mapped)
+            Ok(())
         } else {
-            Err(access_denied())
+            Err(Error::PermissionDenied)
         }
     }
+
+    pub fn size(&self) -> Result<u64, Error> {
+        // Safety: fd is valid
+        unsafe {
+            let mut stat = core::mem::MaybeUninit::<libc::stat>::uninit();
+            if libc::fstat(self.fd, stat.as_mut_ptr()) != 0 {
+                return Err(Error::last_os_error("fstat"));
+            }
+            Ok(stat.assume_init().st_size.try_into().unwrap())
+        }
+    }
+
+    // Objects on hugetlbfs, such as memfds created with `MFD_HUGETLB`, are backed by huge pages.
+    #[cfg(target_os = "linux")]
+    pub fn huge_page_size(&self) -> Result<Option<HugePageSize>, Error> {
+        // Safety: fd is valid
+        let stat = unsafe {
+            let mut stat = core::mem::MaybeUninit::<libc::statfs>::uninit();
+            if libc::fstatfs(self.fd, stat.as_mut_ptr()) != 0 {
+                return Err(Error::last_os_error("fstatfs"));
+            }
+            stat.assume_init()
+        };
+        if stat.f_type as u32 != libc::HUGETLBFS_MAGIC as u32 {
+            return Ok(None);
+        }
+        [HugePageSize::Size2MiB, HugePageSize::Size1GiB]
+            .iter()
+            .copied()
+            .find(|page_size| page_size.size() == stat.f_bsize as usize)
+            .map(Some)
+            .ok_or(Error::HugePagesUnavailable)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn huge_page_size(&self) -> Result<Option<HugePageSize>, Error> {
+        Ok(None)
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn set_len(&self, size: u64) -> Result<(), Error> {
+        // Safety: fd is valid
+        if unsafe { libc::ftruncate(self.fd, size.try_into().map_err(|_| Error::Overflow)?) } == 0 {
+            Ok(())
+        } else {
+            Err(Error::last_os_error("ftruncate"))
+        }
+    }
+
+    pub fn sync_all(&self) -> Result<(), Error> {
+        fsync(self.fd)
+    }
+
+    pub fn handle(&self) -> Result<Handle, Error> {
+        // Safety: fd is valid
+        let fd = unsafe { libc::fcntl(self.fd, libc::F_DUPFD_CLOEXEC, 0) };
+        if fd == -1 {
+            Err(Error::last_os_error("fcntl"))
+        } else {
+            Ok(Handle { fd })
+        }
+    }
+}
+
+fn fsync(fd: libc::c_int) -> Result<(), Error> {
+    // Safety: fd is valid
+    if unsafe { libc::fsync(fd) } == 0 {
+        Ok(())
+    } else {
+        Err(Error::last_os_error("fsync"))
+    }
+}
+
+// A duplicate descriptor of a mapped object, for synchronizing it after the object is dropped.
+#[derive(Debug)]
+pub struct Handle {
+    fd: libc::c_int,
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        // Safety: fd is valid
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+impl Handle {
+    // Called after a synchronous flush, which has already written the data.
+    pub fn sync(&self, metadata: bool) -> Result<(), Error> {
+        if metadata {
+            fsync(self.fd)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod flags {
+    use crate::raw::HugePageSize;
+
+    pub const MAP_LOCKED: libc::c_int = libc::MAP_LOCKED;
+    pub const MAP_POPULATE: libc::c_int = libc::MAP_POPULATE;
+    pub const MAP_NORESERVE: libc::c_int = libc::MAP_NORESERVE;
+    pub const MAP_STACK: libc::c_int = libc::MAP_STACK;
+    // Older kernels ignore this flag and treat the address as a hint, so the address must still
+    // be checked after mapping.
+    #[cfg(target_os = "linux")]
+    pub const MAP_FIXED_NOREPLACE: libc::c_int = libc::MAP_FIXED_NOREPLACE;
+    // Not defined by libc for Android, so the address is only used as a hint.
+    #[cfg(target_os = "android")]
+    pub const MAP_FIXED_NOREPLACE: libc::c_int = 0;
+
+    pub fn huge_page_flags(size: HugePageSize) -> libc::c_int {
+        const MAP_HUGE_SHIFT: u32 = 26;
+        libc::MAP_HUGETLB | (size.shift() << MAP_HUGE_SHIFT) as libc::c_int
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+mod flags {
+    use crate::raw::HugePageSize;
+
+    pub const MAP_LOCKED: libc::c_int = 0;
+    pub const MAP_POPULATE: libc::c_int = 0;
+    pub const MAP_NORESERVE: libc::c_int = 0;
+    pub const MAP_STACK: libc::c_int = 0;
+    pub const MAP_FIXED_NOREPLACE: libc::c_int = 0;
+
+    pub fn huge_page_flags(_size: HugePageSize) -> libc::c_int {
+        0
+    }
+}
+
+use flags::*;
+
+pub fn check_options(options: &MapOptions) -> Result<(), Error> {
+    let supported =
+        cfg!(any(target_os = "linux", target_os = "android")) || options.huge_pages.is_none();
+    if supported {
+        Ok(())
+    } else {
+        Err(Error::Unsupported)
+    }
 }
 
-unsafe fn map_impl<T: ViewImpl>(ptr: *mut u8, view: &T) -> Result<*mut u8, Error> {
+// Flags for the options that are supported directly by `mmap`.
+fn option_flags(options: &MapOptions) -> libc::c_int {
+    let mut flags = 0;
+    if options.lock {
+        flags |= MAP_LOCKED;
+    }
+    if options.populate {
+        flags |= MAP_POPULATE;
+    }
+    if options.no_reserve {
+        flags |= MAP_NORESERVE;
+    }
+    if options.stack {
+        flags |= MAP_STACK;
+    }
+    if let Some(size) = options.huge_pages {
+        flags |= huge_page_flags(size);
+    }
+    flags
+}
+
+// Flags for placing memory at the address hint.
+fn placement_flags(options: &MapOptions) -> libc::c_int {
+    if options.fixed_noreplace {
+        MAP_FIXED_NOREPLACE
+    } else {
+        0
+    }
+}
+
+fn mmap_error(placement: libc::c_int, options: &MapOptions) -> Error {
+    let err = std::io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::EAGAIN) if options.lock => Error::LockLimitExceeded,
+        Some(libc::EEXIST) if placement & MAP_FIXED_NOREPLACE != 0 => Error::AddressUnavailable,
+        _ => Error::from_os_error("mmap", err),
+    }
+}
+
+// The pointer must be an address hint, or with `MAP_FIXED`, must point to a reserved virtual
+// memory region that is replaced by the mapping.
+unsafe fn map_impl<T: ViewImpl>(
+    ptr: *mut u8,
+    placement: libc::c_int,
+    view: &T,
+    options: &MapOptions,
+) -> Result<*mut u8, Error> {
     let mapped = libc::mmap(
         ptr as *mut _,
         view.length().into(),
         view.prot_flags(),
-        view.map_flags(),
+        view.map_flags() | placement | option_flags(options),
         view.object().fd,
         u64::from(view.offset()).try_into().unwrap(),
     );
     if mapped == libc::MAP_FAILED {
-        Err(Error::last_os_error())
+        Err(mmap_error(placement, options))
     } else {
         Ok(mapped as *mut u8)
     }
 }
 
-fn map_multiple_impl<T: ViewImpl>(views: &[T]) -> Result<(*mut u8, usize), Error> {
+// Check that the memory was placed at the address hint, if required.
+fn check_placement(ptr: *mut u8, len: usize, options: &MapOptions) -> Result<(), Error> {
+    if options.fixed_noreplace && ptr as usize != options.address_hint {
+        // Safety: the memory was just mapped
+        unsafe { unmap(ptr, core::iter::once(len)) };
+        Err(Error::AddressUnavailable)
+    } else {
+        Ok(())
+    }
+}
+
+// Apply the options that are not supported by `mmap` on this platform.
+fn finish_map(ptr: *mut u8, len: usize, options: &MapOptions) -> Result<(), Error> {
+    if options.lock && MAP_LOCKED == 0 {
+        // Safety: the memory was just mapped
+        unsafe {
+            if let Err(err) = lock(ptr, len) {
+                unmap(ptr, core::iter::once(len));
+                return Err(err);
+            }
+        }
+    }
+    if options.populate && MAP_POPULATE == 0 {
+        // Populating is only an optimization, so the advice is allowed to fail.
+        // Safety: the memory was just mapped
+        let _ = unsafe { advise(ptr, len, Advice::WillNeed) };
+    }
+    Ok(())
+}
+
+fn map_single_impl<T: ViewImpl>(view: &T, options: &MapOptions) -> Result<*mut u8, Error> {
+    let len = view.length().into();
+    // Safety: the pointer is only a hint, and existing mappings are never replaced.
+    let ptr = unsafe {
+        map_impl(
+            options.address_hint as *mut u8,
+            placement_flags(options),
+            view,
+            options,
+        )?
+    };
+    check_placement(ptr, len, options)?;
+    finish_map(ptr, len, options)?;
+    Ok(ptr)
+}
+
+// The pointer must be an address hint.
+fn reserve_impl(ptr: *mut u8, len: usize, placement: libc::c_int) -> Result<*mut u8, Error> {
+    let fd = open_anonymous(len.try_into().unwrap())?;
+    // Safety: the pointer is only a hint, and existing mappings are never replaced.
+    let ptr = unsafe {
+        libc::mmap(
+            ptr as *mut _,
+            len,
+            libc::PROT_NONE,
+            libc::MAP_SHARED | placement,
+            fd,
+            0,
+        )
+    };
+    let ptr = if ptr == libc::MAP_FAILED {
+        Err(mmap_error(placement, &MapOptions::new()))
+    } else {
+        Ok(ptr as *mut u8)
+    };
+    // Safety: fd is valid
+    unsafe { libc::close(fd) };
+    ptr
+}
+
+// Reserve a region of address space at the address hint if possible, aligned for the views.
+fn reserve(len: usize, options: &MapOptions) -> Result<*mut u8, Error> {
+    if options.address_hint != 0 {
+        let hint = options.address_hint as *mut u8;
+        let ptr = reserve_impl(hint, len, placement_flags(options))?;
+        if ptr == hint || (!options.fixed_noreplace && options.huge_pages.is_none()) {
+            return Ok(ptr);
+        }
+        // Safety: the memory was just reserved
+        unsafe { unmap(ptr, core::iter::once(len)) };
+        if options.fixed_noreplace {
+            return Err(Error::AddressUnavailable);
+        }
+    }
+
+    let alignment = options.alignment();
+    if alignment <= page_size().get() {
+        return reserve_impl(core::ptr::null_mut(), len, 0);
+    }
+
+    // Reserve extra space, and release the excess on either side of the aligned region
+    let padded = len.checked_add(alignment).ok_or(Error::Overflow)?;
+    let ptr = reserve_impl(core::ptr::null_mut(), padded, 0)?;
+    let head = (alignment - ptr as usize % alignment) % alignment;
+    let tail = padded - head - len;
+    // Safety: the excess regions are within the reservation
+    unsafe {
+        if head != 0 {
+            unmap(ptr, core::iter::once(head));
+        }
+        unmap(ptr.add(head + len), core::iter::once(tail));
+        Ok(ptr.add(head))
+    }
+}
+
+fn map_multiple_impl<T: ViewImpl>(
+    views: &[T],
+    options: &MapOptions,
+) -> Result<(*mut u8, usize), Error> {
     // Allocate mapping
     let len = views
         .iter()
         .fold(0, |length, view| length + usize::from(view.length()));
-    let ptr = {
-        let fd = open_anonymous(len.try_into().unwrap())?;
-        // Safety: pointer is selected by kernel
-        let ptr = unsafe {
-            libc::mmap(
-                core::ptr::null_mut(),
-                len,
-                libc::PROT_NONE,
-                libc::MAP_SHARED,
-                fd,
-                0,
-            )
-        };
-        let ptr = if ptr == libc::MAP_FAILED {
-            Err(Error::last_os_error())
-        } else {
-            Ok(ptr as *mut u8)
-        };
-        // Safety: fd is valid
-        unsafe { libc::close(fd) };
-        ptr
-    }?;
+    let ptr = reserve(len, options)?;
 
     let mut offset = 0;
     for view in views {
         // Safety: pointer is within previously allocated range
         unsafe {
-            map_impl(ptr.add(offset), view)?;
+            if let Err(err) = map_impl(ptr.add(offset), libc::MAP_FIXED, view, options) {
+                unmap(ptr, core::iter::once(len));
+                return Err(err);
+            }
         }
         offset += usize::from(view.length());
     }
+    finish_map(ptr, len, options)?;
     Ok((ptr, len))
 }
 
-pub fn map(view: &View<'_>) -> Result<(*const u8, usize), Error> {
+pub fn map(view: &View<'_>, options: &MapOptions) -> Result<(*const u8, usize), Error> {
     Ok((
-        // Safety: the pointer is selected by the kernel.
-        unsafe { map_impl(core::ptr::null_mut(), view)? as *const u8 },
+        map_single_impl(view, options)? as *const u8,
         view.length().into(),
     ))
 }
 
-pub fn map_mut(view: &ViewMut<'_>) -> Result<(*mut u8, usize), Error> {
-    Ok((
-        // Safety: the pointer is selected by the kernel.
-        unsafe { map_impl(core::ptr::null_mut(), view)? },
+pub fn map_mut(view: &ViewMut<'_>, options: &MapOptions) -> Result<(*mut u8, usize), Error> {
+    Ok((map_single_impl(view, options)?, view.length().into()))
+}
+
+pub fn map_multiple(views: &[View<'_>], options: &MapOptions) -> Result<(*const u8, usize), Error> {
+    map_multiple_impl(views, options).map(|(ptr, len)| (ptr as *const u8, len))
+}
+
+pub fn map_multiple_mut(
+    views: &[ViewMut<'_>],
+    options: &MapOptions,
+) -> Result<(*mut u8, usize), Error> {
+    map_multiple_impl(views, options)
+}
+
+// The memory map must be a single view of the same object at the same offset, which callers
+// check by the object's unique id.
+// If remapping fails, the original memory map is left unchanged.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn remap_impl<T: ViewImpl>(ptr: *mut u8, len: usize, view: &T) -> Result<*mut u8, Error> {
+    // The existing mapping already refers to the view's object, so only its length changes
+    let remapped = libc::mremap(
+        ptr as *mut _,
+        len,
         view.length().into(),
-    ))
+        libc::MREMAP_MAYMOVE,
+    );
+    if remapped == libc::MAP_FAILED {
+        Err(Error::last_os_error("mremap"))
+    } else {
+        Ok(remapped as *mut u8)
+    }
+}
+
+// The original memory map is only unmapped once the view is mapped at its new address.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+unsafe fn remap_impl<T: ViewImpl>(ptr: *mut u8, len: usize, view: &T) -> Result<*mut u8, Error> {
+    let new_len: usize = view.length().into();
+    if new_len <= len {
+        if new_len < len {
+            unmap(ptr.add(new_len), core::iter::once(len - new_len));
+        }
+        return Ok(ptr);
+    }
+
+    // Try to extend the mapping in place
+    let options = MapOptions::new();
+    let tail = Tail {
+        view,
+        offset: view.offset().checked_add(Length::exact(len)?)?,
+        length: Length::exact(new_len - len)?,
+    };
+    if let Ok(extension) = map_impl(ptr.add(len), 0, &tail, &options) {
+        if extension == ptr.add(len) {
+            return Ok(ptr);
+        }
+        unmap(extension, core::iter::once(new_len - len));
+    }
+
+    // Otherwise, move the mapping
+    let remapped = map_impl(core::ptr::null_mut(), 0, view, &options)?;
+    if view.map_flags() == libc::MAP_PRIVATE {
+        // Private modifications are only preserved by copying them
+        let _ = protect(ptr, len, Protection::Read, true);
+        core::ptr::copy_nonoverlapping(ptr, remapped, len);
+    }
+    unmap(ptr, core::iter::once(len));
+    Ok(remapped)
+}
+
+pub unsafe fn remap(ptr: *const u8, len: usize, view: &View<'_>) -> Result<*const u8, Error> {
+    remap_impl(ptr as *mut u8, len, view).map(|ptr| ptr as *const u8)
+}
+
+pub unsafe fn remap_mut(ptr: *mut u8, len: usize, view: &ViewMut<'_>) -> Result<*mut u8, Error> {
+    remap_impl(ptr, len, view)
+}
+
+// Maps inaccessible anonymous memory, replacing any existing mapping if `placement` is `MAP_FIXED`.
+unsafe fn map_inaccessible(
+    ptr: *mut u8,
+    len: usize,
+    placement: libc::c_int,
+) -> Result<*mut u8, Error> {
+    let mapped = libc::mmap(
+        ptr as *mut _,
+        len,
+        libc::PROT_NONE,
+        libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | MAP_NORESERVE | placement,
+        -1,
+        0,
+    );
+    if mapped == libc::MAP_FAILED {
+        Err(Error::last_os_error("mmap"))
+    } else {
+        Ok(mapped as *mut u8)
+    }
+}
+
+pub fn reserve_address_space(len: usize) -> Result<*mut u8, Error> {
+    // Safety: the kernel selects an unused region
+    unsafe { map_inaccessible(core::ptr::null_mut(), len, 0) }
 }
 
-pub fn map_multiple(views: &[View<'_>]) -> Result<(*const u8, usize), Error> {
-    map_multiple_impl(views).map(|(ptr, len)| (ptr as *const u8, len))
+pub unsafe fn commit(ptr: *mut u8, len: usize) -> Result<(), Error> {
+    protect(ptr, len, Protection::ReadWrite, false)
 }
 
-pub fn map_multiple_mut(views: &[ViewMut<'_>]) -> Result<(*mut u8, usize), Error> {
-    map_multiple_impl(views)
+pub unsafe fn decommit(ptr: *mut u8, len: usize) -> Result<(), Error> {
+    // Replacing the pages releases them, along with any mapped views
+    map_inaccessible(ptr, len, libc::MAP_FIXED).map(|_| ())
+}
+
+pub unsafe fn release(ptr: *mut u8, len: usize) {
+    unmap(ptr, core::iter::once(len))
+}
+
+pub fn allocate(len: usize, sparse: bool) -> Result<*mut u8, Error> {
+    let flags = if sparse { MAP_NORESERVE } else { 0 };
+    // Safety: the kernel selects an unused region
+    let ptr = unsafe {
+        libc::mmap(
+            core::ptr::null_mut(),
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | flags,
+            -1,
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        Err(Error::last_os_error("mmap"))
+    } else {
+        Ok(ptr as *mut u8)
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub unsafe fn reallocate(ptr: *mut u8, old_len: usize, new_len: usize) -> Result<*mut u8, Error> {
+    let remapped = libc::mremap(ptr as *mut _, old_len, new_len, libc::MREMAP_MAYMOVE);
+    if remapped == libc::MAP_FAILED {
+        Err(Error::last_os_error("mremap"))
+    } else {
+        Ok(remapped as *mut u8)
+    }
+}
+
+// Allocations can only be shrunk in place.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub unsafe fn reallocate(ptr: *mut u8, old_len: usize, new_len: usize) -> Result<*mut u8, Error> {
+    if new_len < old_len {
+        unmap(ptr.add(new_len), core::iter::once(old_len - new_len));
+        Ok(ptr)
+    } else {
+        Err(Error::Unsupported)
+    }
+}
+
+// Private anonymous pages read as zeros after `MADV_DONTNEED` on Linux, but not elsewhere.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub unsafe fn discard(ptr: *mut u8, len: usize) -> Result<(), Error> {
+    advise(ptr, len, Advice::DontNeed)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub unsafe fn discard(ptr: *mut u8, len: usize) -> Result<(), Error> {
+    // Replacing the pages releases them
+    let mapped = libc::mmap(
+        ptr as *mut _,
+        len,
+        libc::PROT_READ | libc::PROT_WRITE,
+        libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED,
+        -1,
+        0,
+    );
+    if mapped == libc::MAP_FAILED {
+        Err(Error::last_os_error("mmap"))
+    } else {
+        Ok(())
+    }
+}
+
+pub unsafe fn deallocate(ptr: *mut u8, len: usize) {
+    unmap(ptr, core::iter::once(len))
+}
+
+unsafe fn map_fixed_impl<T: ViewImpl>(
+    ptr: *mut u8,
+    view: &T,
+    options: &MapOptions,
+) -> Result<(), Error> {
+    let len = view.length().into();
+    map_impl(ptr, libc::MAP_FIXED, view, options)?;
+    if let Err(err) = finish_map(ptr, len, options) {
+        // The view was unmapped, so restore the reservation
+        let _ = decommit(ptr, len);
+        return Err(err);
+    }
+    Ok(())
+}
+
+pub unsafe fn map_fixed(ptr: *mut u8, view: &View<'_>, options: &MapOptions) -> Result<(), Error> {
+    map_fixed_impl(ptr, view, options)
+}
+
+pub unsafe fn map_fixed_mut(
+    ptr: *mut u8,
+    view: &ViewMut<'_>,
+    options: &MapOptions,
+) -> Result<(), Error> {
+    map_fixed_impl(ptr, view, options)
 }
 
 pub unsafe fn unmap(ptr: *mut u8, view_lengths: impl Iterator<Item = usize>) {
     assert_eq!(libc::munmap(ptr as *mut _, view_lengths.sum()), 0);
 }
 
-fn page_size() -> u64 {
+pub fn page_size() -> NonZeroUsize {
     // Safety: cannot fail
     let val = unsafe { libc::sysconf(libc::_SC_PAGE_SIZE) };
     assert!(val != -1);
-    val.try_into().unwrap()
+    NonZeroUsize::new(val.try_into().unwrap()).unwrap()
 }
 
 pub fn offset_granularity() -> NonZeroUsize {
-    NonZeroUsize::new(page_size().try_into().unwrap()).unwrap()
+    page_size()
 }
 
 pub fn length_granularity() -> NonZeroUsize {
-    NonZeroUsize::new(page_size().try_into().unwrap()).unwrap()
+    page_size()
+}
+
+pub unsafe fn protect(
+    ptr: *mut u8,
+    len: usize,
+    protection: Protection,
+    _copy_on_write: bool,
+) -> Result<(), Error> {
+    let prot_flags = match protection {
+        Protection::None => libc::PROT_NONE,
+        Protection::Read => libc::PROT_READ,
+        Protection::ReadWrite => libc::PROT_READ | libc::PROT_WRITE,
+        Protection::ReadExecute => libc::PROT_READ | libc::PROT_EXEC,
+    };
+    if libc::mprotect(ptr as *mut _, len, prot_flags) == 0 {
+        Ok(())
+    } else {
+        Err(Error::last_os_error("mprotect"))
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub unsafe fn flush_instruction_cache(_ptr: *const u8, _len: usize) {
+    // Instruction caches are coherent with data caches
+}
+
+#[cfg(all(
+    not(any(target_arch = "x86", target_arch = "x86_64")),
+    any(target_os = "macos", target_os = "ios")
+))]
+pub unsafe fn flush_instruction_cache(ptr: *const u8, len: usize) {
+    extern "C" {
+        fn sys_icache_invalidate(start: *mut libc::c_void, len: libc::size_t);
+    }
+    sys_icache_invalidate(ptr as *mut _, len)
+}
+
+#[cfg(not(any(
+    target_arch = "x86",
+    target_arch = "x86_64",
+    target_os = "macos",
+    target_os = "ios"
+)))]
+pub unsafe fn flush_instruction_cache(ptr: *const u8, len: usize) {
+    extern "C" {
+        fn __clear_cache(start: *mut libc::c_char, end: *mut libc::c_char);
+    }
+    __clear_cache(ptr as *mut _, ptr.add(len) as *mut _)
+}
+
+pub unsafe fn advise(ptr: *mut u8, len: usize, advice: Advice) -> Result<(), Error> {
+    let advice = match advice {
+        Advice::Normal => libc::MADV_NORMAL,
+        Advice::Sequential => libc::MADV_SEQUENTIAL,
+        Advice::Random => libc::MADV_RANDOM,
+        Advice::WillNeed => libc::MADV_WILLNEED,
+        Advice::DontNeed => libc::MADV_DONTNEED,
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd"
+        ))]
+        Advice::Free => libc::MADV_FREE,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        Advice::HugePage => libc::MADV_HUGEPAGE,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        Advice::NoHugePage => libc::MADV_NOHUGEPAGE,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        Advice::DontFork => libc::MADV_DONTFORK,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        Advice::DoFork => libc::MADV_DOFORK,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        Advice::DontDump => libc::MADV_DONTDUMP,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        Advice::DoDump => libc::MADV_DODUMP,
+        #[allow(unreachable_patterns)]
+        _ => return Err(Error::Unsupported),
+    };
+    if libc::madvise(ptr as *mut _, len, advice) == 0 {
+        Ok(())
+    } else {
+        Err(Error::last_os_error("madvise"))
+    }
+}
+
+pub unsafe fn flush(ptr: *mut u8, len: usize, asynchronous: bool) -> Result<(), Error> {
+    let flags = if asynchronous {
+        libc::MS_ASYNC
+    } else {
+        libc::MS_SYNC
+    };
+    if libc::msync(ptr as *mut _, len, flags) == 0 {
+        Ok(())
+    } else {
+        Err(Error::last_os_error("msync"))
+    }
+}
+
+fn lock_error(syscall: &'static str) -> Error {
+    let err = std::io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::ENOMEM) | Some(libc::EAGAIN) => Error::LockLimitExceeded,
+        Some(libc::EPERM) => Error::LockNotPermitted,
+        _ => Error::from_os_error(syscall, err),
+    }
+}
+
+pub unsafe fn lock(ptr: *mut u8, len: usize) -> Result<(), Error> {
+    if libc::mlock(ptr as *const _, len) == 0 {
+        Ok(())
+    } else {
+        Err(lock_error("mlock"))
+    }
+}
+
+pub unsafe fn unlock(ptr: *mut u8, len: usize) -> Result<(), Error> {
+    if libc::munlock(ptr as *const _, len) == 0 {
+        Ok(())
+    } else {
+        Err(Error::last_os_error("munlock"))
+    }
+}
+
+pub fn lock_all(current: bool, future: bool) -> Result<(), Error> {
+    let mut flags = 0;
+    if current {
+        flags |= libc::MCL_CURRENT;
+    }
+    if future {
+        flags |= libc::MCL_FUTURE;
+    }
+    // Safety: locking memory has no effect on its contents
+    if unsafe { libc::mlockall(flags) } == 0 {
+        Ok(())
+    } else {
+        Err(lock_error("mlockall"))
+    }
+}
+
+pub fn unlock_all() -> Result<(), Error> {
+    // Safety: unlocking memory has no effect on its contents
+    if unsafe { libc::munlockall() } == 0 {
+        Ok(())
+    } else {
+        Err(Error::last_os_error("munlockall"))
+    }
 }
@@ -1,8 +1,11 @@
 //! Views of objects mapped to shared memory.
 
-use super::map_impl;
+use super::{map_impl, Error, HugePageSize};
 use once_cell::race::OnceNonZeroUsize;
-use std::{convert::TryInto, io::Error};
+use std::{
+    convert::TryInto,
+    sync::{Mutex, PoisonError},
+};
 
 /// Permissions for file mapping.
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -13,6 +16,21 @@ pub struct FilePermissions {
     pub execute: bool,
 }
 
+/// Seals that restrict how an object can be modified.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Seals {
+    /// The object can't be shrunk.
+    pub shrink: bool,
+    /// The object can't be grown.
+    pub grow: bool,
+    /// The object can't be written, or mapped writable.
+    pub write: bool,
+    /// The object can't be mapped writable, but existing writable mappings remain writable.
+    pub future_write: bool,
+    /// No more seals can be added.
+    pub seal: bool,
+}
+
 /// Permissions for a particular view of an object.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum ReadPermissions {
@@ -35,9 +53,12 @@ pub enum WritePermissions {
 #[derive(Debug)]
 pub struct Object {
     inner: map_impl::Object,
-    size: u64,
+    // Locked while resizing, so concurrent resizes never shrink the object
+    size: Mutex<u64>,
     write: bool,
     execute: bool,
+    sparse: bool,
+    huge_pages: Option<HugePageSize>,
 }
 
 impl Object {
@@ -48,12 +69,137 @@ impl Object {
         let execute = permissions == ReadPermissions::Execute;
         Ok(Self {
             inner: map_impl::Object::anonymous(size, execute)?,
-            size: size.try_into().unwrap(),
+            size: Mutex::new(size.try_into().unwrap()),
             write: true,
             execute,
+            sparse: false,
+            huge_pages: None,
+        })
+    }
+
+    /// Create an anonymous shared memory of `size` bytes, backed by huge pages.
+    ///
+    /// The size is rounded up to a multiple of the huge page size, and the offset and length of
+    /// each view must be multiples of the huge page size.
+    /// If huge pages of the requested size are not configured or not enough are free, returns
+    /// [`Error::HugePagesUnavailable`].
+    /// Huge pages are only supported on Linux.
+    ///
+    /// This memory region is always writable.
+    pub fn anonymous_huge(
+        size: usize,
+        page_size: HugePageSize,
+        permissions: ReadPermissions,
+    ) -> Result<Self, Error> {
+        let execute = permissions == ReadPermissions::Execute;
+        let size = super::round_up(size as u64, page_size.size() as u64)?;
+        let size_bytes = size.try_into().map_err(|_| Error::Overflow)?;
+        Ok(Self {
+            inner: map_impl::Object::anonymous_huge(size_bytes, page_size, execute)?,
+            size: Mutex::new(size),
+            write: true,
+            execute,
+            sparse: false,
+            huge_pages: Some(page_size),
+        })
+    }
+
+    /// Create an anonymous shared memory of `size` bytes that can be sealed.
+    ///
+    /// This memory region is writable until it is sealed with [`seal`](`Self::seal`).
+    /// Sealing is only supported on Linux.
+    pub fn anonymous_sealed(size: usize, permissions: ReadPermissions) -> Result<Self, Error> {
+        let execute = permissions == ReadPermissions::Execute;
+        Ok(Self {
+            inner: map_impl::Object::anonymous_sealed(size, execute)?,
+            size: Mutex::new(size.try_into().unwrap()),
+            write: true,
+            execute,
+            sparse: false,
+            huge_pages: None,
+        })
+    }
+
+    /// Add seals to the object.
+    ///
+    /// Seals can't be removed once added.
+    /// Sealing against writes fails if the object has any shared writable mappings, including
+    /// detached mappings, and prevents creating writable views other than copy-on-write views.
+    pub fn seal(&mut self, seals: Seals) -> Result<(), Error> {
+        self.inner.add_seals(seals)?;
+        if seals.write || seals.future_write {
+            self.write = false;
+        }
+        Ok(())
+    }
+
+    /// The seals applied to the object.
+    ///
+    /// Objects that don't support sealing have no seals.
+    pub fn seals(&self) -> Result<Seals, Error> {
+        self.inner.seals()
+    }
+
+    /// Create a named shared memory object of `size` bytes.
+    ///
+    /// Other processes can open the object by name with [`open_named`](`Self::open_named`).
+    /// If an object with the same name already exists, returns an error.
+    ///
+    /// On Unix, the object is created with the file mode `mode` (such as `0o600`), and exists
+    /// until it is removed with [`unlink_named`](`Self::unlink_named`).
+    /// Names should begin with a slash and contain no other slashes.
+    /// On Windows, `mode` is ignored, and the object is destroyed once it is no longer open.
+    ///
+    /// This memory region is always writable.
+    ///
+    /// # Safety
+    /// Other processes that open the object can modify or truncate it.
+    /// See [`FileOptions::new`] for the consequences.
+    pub unsafe fn create_named(
+        name: &str,
+        size: usize,
+        permissions: ReadPermissions,
+        mode: u32,
+    ) -> Result<Self, Error> {
+        let execute = permissions == ReadPermissions::Execute;
+        Ok(Self {
+            inner: map_impl::Object::create_named(name, size, execute, mode)?,
+            size: Mutex::new(size.try_into().unwrap()),
+            write: true,
+            execute,
+            sparse: false,
+            huge_pages: None,
+        })
+    }
+
+    /// Open an existing named shared memory object.
+    ///
+    /// On Windows, the size of the object is rounded up to the page size.
+    ///
+    /// # Safety
+    /// Other processes that open the object can modify or truncate it.
+    /// See [`FileOptions::new`] for the consequences.
+    pub unsafe fn open_named(name: &str, permissions: FilePermissions) -> Result<Self, Error> {
+        let (inner, size) =
+            map_impl::Object::open_named(name, permissions.write, permissions.execute)?;
+        Ok(Self {
+            inner,
+            size: Mutex::new(size),
+            write: permissions.write,
+            execute: permissions.execute,
+            sparse: false,
+            huge_pages: None,
         })
     }
 
+    /// Remove a named shared memory object.
+    ///
+    /// The object remains valid for anyone who already has it open, but can't be opened again.
+    /// On Windows, this has no effect, since objects are destroyed once they are no longer open.
+    pub fn unlink_named(name: &str) -> Result<(), Error> {
+        map_impl::Object::unlink_named(name)
+    }
+
     /// Map an existing file to memory.
     ///
     /// # Safety
@@ -62,48 +208,262 @@ impl Object {
         FileOptions::new(file)
     }
 
+    /// Grow the object to `size` bytes.
+    ///
+    /// Since the object only grows, it can be resized while it is mapped.
+    /// Existing memory maps are not extended, but can be remapped with
+    /// [`Mapping::remap`](`super::Mapping::remap`) or
+    /// [`MappingMut::remap`](`super::MappingMut::remap`).
+    /// Objects can't be shrunk, since memory maps may still refer to the truncated pages, so a
+    /// smaller size returns [`Error::WouldTruncate`].
+    /// On Windows, only objects backed by files can be resized.
+    /// The size of objects backed by huge pages is rounded up to a multiple of the huge page size.
+    pub fn set_len(&self, size: u64) -> Result<(), Error> {
+        let mut current = self.size.lock().unwrap_or_else(PoisonError::into_inner);
+        if size < *current {
+            return Err(Error::WouldTruncate);
+        }
+        if !self.write {
+            return Err(Error::PermissionDenied);
+        }
+        // Objects backed by huge pages must be a multiple of the huge page size
+        let size = match self.huge_pages {
+            Some(page_size) => super::round_up(size, page_size.size() as u64)?,
+            None => size,
+        };
+        self.inner.set_len(size)?;
+        *current = size;
+        Ok(())
+    }
+
+    /// Write all modifications and metadata of the object to storage.
+    ///
+    /// Modifications made through memory maps must be flushed first, with
+    /// [`MappingMut::flush`](`super::MappingMut::flush`).
+    /// This has no effect on anonymous objects.
+    pub fn sync_all(&self) -> Result<(), Error> {
+        self.inner.sync_all()
+    }
+
+    /// The size of the huge pages backing the object, if any.
+    pub fn huge_page_size(&self) -> Option<HugePageSize> {
+        self.huge_pages
+    }
+
+    /// Offsets of views of the object must be a multiple of this value.
+    ///
+    /// This is the huge page size for objects backed by huge pages, and
+    /// [`Offset::granularity`] otherwise.
+    pub fn offset_granularity(&self) -> u64 {
+        self.huge_pages
+            .map_or_else(Offset::granularity, |page_size| page_size.size() as u64)
+    }
+
+    /// Lengths of views of the object must be a multiple of this value.
+    ///
+    /// This is the huge page size for objects backed by huge pages, and
+    /// [`Length::granularity`] otherwise.
+    pub fn length_granularity(&self) -> usize {
+        self.huge_pages
+            .map_or_else(Length::granularity, HugePageSize::size)
+    }
+
+    /// The size of the object, in bytes.
+    pub fn size(&self) -> u64 {
+        *self.size.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The permissions allowed for views of the object.
+    pub fn permissions(&self) -> FilePermissions {
+        FilePermissions {
+            write: self.write,
+            execute: self.execute,
+        }
+    }
+
+    fn check_bounds(&self, offset: Offset, length: Length) -> Result<(), Error> {
+        if !super::is_aligned(offset.to_u64(), self.offset_granularity())
+            || !super::is_aligned(length.to_usize() as u64, self.length_granularity() as u64)
+        {
+            return Err(Error::Misaligned);
+        }
+        if self.sparse {
+            return Ok(());
+        }
+        // The final page of the object may be partially filled
+        let size = self.size();
+        let limit = super::round_up(size, self.length_granularity() as u64).unwrap_or(u64::MAX);
+        let end = offset.to_u64().saturating_add(length.to_usize() as u64);
+        if end <= limit {
+            Ok(())
+        } else {
+            Err(Error::OutOfBounds { end, size })
+        }
+    }
+
     /// Create a view of the mapped object.
     ///
-    /// Returns `None` if the requested permissions are not allowed for this object.
+    /// Returns an error if the requested permissions are not allowed for this object, if the
+    /// view is not aligned to the object's huge pages, or if the view extends past the end of the
+    /// object.
     pub fn view(
         &self,
         offset: Offset,
         length: Length,
         permissions: ReadPermissions,
-    ) -> Option<View<'_>> {
+    ) -> Result<View<'_>, Error> {
         let execute = permissions == ReadPermissions::Execute;
-        if !execute || self.execute {
-            Some(View {
-                offset,
-                length,
-                execute,
-                object: &self.inner,
-            })
-        } else {
-            None
+        if execute && !self.execute {
+            return Err(Error::PermissionDenied);
         }
+        self.check_bounds(offset, length)?;
+        Ok(View {
+            offset,
+            length,
+            execute,
+            permissions: self.permissions(),
+            huge_pages: self.huge_pages,
+            object: &self.inner,
+        })
     }
 
     /// Create a mutable view of the mapped object.
     ///
-    /// Returns `None` if the requested permissions are not allowed for this object.
+    /// Returns an error if the requested permissions are not allowed for this object, if the
+    /// view is not aligned to the object's huge pages, or if the view extends past the end of the
+    /// object.
     pub fn view_mut(
         &self,
         offset: Offset,
         length: Length,
         permissions: WritePermissions,
-    ) -> Option<ViewMut<'_>> {
+    ) -> Result<ViewMut<'_>, Error> {
         let copy_on_write = permissions == WritePermissions::CopyOnWrite;
-        if copy_on_write || self.write {
-            Some(ViewMut {
-                offset,
-                length,
-                copy_on_write,
-                object: &self.inner,
-            })
-        } else {
-            None
+        if !copy_on_write && !self.write {
+            return Err(Error::PermissionDenied);
         }
+        self.check_bounds(offset, length)?;
+        Ok(ViewMut {
+            offset,
+            length,
+            copy_on_write,
+            permissions: self.permissions(),
+            huge_pages: self.huge_pages,
+            object: &self.inner,
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Object {
+    /// Create an object from a file descriptor, such as a memfd received from another process.
+    ///
+    /// Files on hugetlbfs, such as memfds created with `MFD_HUGETLB`, are backed by huge pages.
+    /// Returns [`Error::PermissionDenied`] if the file descriptor doesn't allow the requested
+    /// permissions.
+    ///
+    /// # Safety
+    /// See [`FileOptions::new`].
+    pub unsafe fn from_owned_fd(
+        fd: std::os::unix::io::OwnedFd,
+        permissions: FilePermissions,
+    ) -> Result<Self, Error> {
+        use std::os::unix::io::IntoRawFd;
+        let inner = map_impl::Object::from_raw_fd(fd.into_raw_fd());
+        inner.check_permissions(permissions.write)?;
+        Self::from_inner(inner, permissions)
+    }
+
+    fn from_inner(inner: map_impl::Object, permissions: FilePermissions) -> Result<Self, Error> {
+        Ok(Self {
+            size: Mutex::new(inner.size()?),
+            huge_pages: inner.huge_page_size()?,
+            inner,
+            write: permissions.write,
+            execute: permissions.execute,
+            sparse: false,
+        })
+    }
+
+    /// Create an object from a sealed file descriptor, such as a memfd received from another
+    /// process.
+    ///
+    /// Unlike [`from_owned_fd`](`Self::from_owned_fd`), this is safe, because the object must be
+    /// sealed against shrinking and writing, so no other process can truncate or modify it.
+    /// Returns [`Error::NotSealed`] if the object isn't sealed, or [`Error::PermissionDenied`]
+    /// if write permissions are requested.
+    pub fn from_sealed_fd(
+        fd: std::os::unix::io::OwnedFd,
+        permissions: FilePermissions,
+    ) -> Result<Self, Error> {
+        use std::os::unix::io::IntoRawFd;
+        // Safety: the object is checked for seals before it is used
+        let inner = unsafe { map_impl::Object::from_raw_fd(fd.into_raw_fd()) };
+        let seals = inner.seals()?;
+        if !(seals.shrink && seals.write) {
+            return Err(Error::NotSealed);
+        }
+        if permissions.write {
+            return Err(Error::PermissionDenied);
+        }
+        inner.check_permissions(false)?;
+        Self::from_inner(inner, permissions)
+    }
+
+    /// Borrow the file descriptor.
+    ///
+    /// This isn't [`AsFd`](`std::os::unix::io::AsFd`), since the file descriptor can be used to
+    /// truncate or write to the object while it is mapped.
+    ///
+    /// # Safety
+    /// The file descriptor must not be used to truncate the object, or to modify it in a way
+    /// that is not allowed by its mappings, such as writing to memory that is borrowed as `&[u8]`.
+    pub unsafe fn borrow_fd(&self) -> std::os::unix::io::BorrowedFd<'_> {
+        std::os::unix::io::BorrowedFd::borrow_raw(self.inner.as_raw_fd())
+    }
+}
+
+/// The object is writable if the file descriptor allows it, and is never executable.
+///
+/// Prefer [`Object::from_owned_fd`], which returns an error rather than panicking.
+///
+/// # Safety
+/// In addition to owning the file descriptor, see [`FileOptions::new`].
+///
+/// # Panics
+/// Panics if the size of the object or the size of its huge pages can't be determined.
+#[cfg(unix)]
+impl std::os::unix::io::FromRawFd for Object {
+    unsafe fn from_raw_fd(fd: std::os::unix::io::RawFd) -> Self {
+        let inner = map_impl::Object::from_raw_fd(fd);
+        let permissions = FilePermissions {
+            write: inner.check_permissions(true).is_ok(),
+            execute: false,
+        };
+        Self::from_inner(inner, permissions).expect("couldn't determine the object size")
+    }
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for Object {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::IntoRawFd for Object {
+    fn into_raw_fd(self) -> std::os::unix::io::RawFd {
+        self.inner.into_raw_fd()
+    }
+}
+
+#[cfg(unix)]
+impl From<Object> for std::os::unix::io::OwnedFd {
+    fn from(object: Object) -> Self {
+        use std::os::unix::io::FromRawFd;
+        // Safety: the file descriptor is owned by the object
+        unsafe { Self::from_raw_fd(object.inner.into_raw_fd()) }
     }
 }
 
@@ -112,6 +472,7 @@ pub struct FileOptions<'a> {
     file: &'a std::fs::File,
     write: bool,
     execute: bool,
+    sparse: bool,
 }
 
 impl<'a> FileOptions<'a> {
@@ -135,6 +496,7 @@ impl<'a> FileOptions<'a> {
             file,
             write: false,
             execute: false,
+            sparse: false,
         }
     }
 
@@ -150,17 +512,28 @@ impl<'a> FileOptions<'a> {
         self
     }
 
+    /// Allow views to extend past the end of the file.
+    ///
+    /// This is useful for files that are grown after they are mapped.
+    /// Accessing a page that is entirely past the end of the file can result in SIGBUS.
+    pub fn sparse(&mut self, sparse: bool) -> &mut Self {
+        self.sparse = sparse;
+        self
+    }
+
     /// Open a mapping object with the specified options.
     pub fn finish(&self) -> Result<Object, Error> {
-        let size = self.file.metadata()?.len();
+        let size = map_impl::file_size(self.file)?;
         // Safety: unsafe is pushed off to `new`
         let inner =
             unsafe { map_impl::Object::with_file(self.file, size, self.write, self.execute)? };
         Ok(Object {
             inner,
-            size,
+            size: Mutex::new(size),
             write: self.write,
             execute: self.execute,
+            sparse: self.sparse,
+            huge_pages: None,
         })
     }
 }
@@ -176,32 +549,48 @@ impl Offset {
         GRANULARITY.get_or_init(map_impl::offset_granularity).get() as u64
     }
 
+    /// Returns `true` if the value is a multiple of [`granularity`](`Self::granularity`).
+    pub fn is_aligned(value: u64) -> bool {
+        super::is_aligned(value, Self::granularity())
+    }
+
     /// Create an offset with the specified value.
     ///
-    /// If the value is not a multiple of [`granularity`](`Self::granularity`), returns `None`.
-    pub fn exact(value: u64) -> Option<Self> {
-        assert!(value != 0, "length must not be zero");
-        if value % Self::granularity() == 0 {
-            Some(Self(value))
+    /// If the value is not a multiple of [`granularity`](`Self::granularity`), returns
+    /// [`Error::Misaligned`].
+    pub fn exact(value: u64) -> Result<Self, Error> {
+        if Self::is_aligned(value) {
+            Ok(Self(value))
         } else {
-            None
+            Err(Error::Misaligned)
         }
     }
 
     /// Create an offset, rounded up to the next possible value.
-    pub fn round_up(value: u64) -> Self {
-        assert!(value != 0, "length must not be zero");
-        Self::exact(
-            value.checked_add(Self::granularity() - 1).unwrap() / Self::granularity()
-                * Self::granularity(),
-        )
-        .unwrap()
+    ///
+    /// If the rounded value is not representable, returns [`Error::Overflow`].
+    pub fn round_up(value: u64) -> Result<Self, Error> {
+        let granularity = Self::granularity();
+        value
+            .checked_add(granularity - 1)
+            .map(|value| Self(value / granularity * granularity))
+            .ok_or(Error::Overflow)
     }
 
     /// Create an offset, rounded down to the next possible value.
     pub fn round_down(value: u64) -> Self {
-        assert!(value != 0, "length must not be zero");
-        Self::exact(value / Self::granularity() * Self::granularity()).unwrap()
+        Self(value / Self::granularity() * Self::granularity())
+    }
+
+    /// Add a length to the offset.
+    ///
+    /// Lengths are always a multiple of the offset granularity, so the result remains aligned.
+    /// If the result is not representable, returns [`Error::Overflow`].
+    pub fn checked_add(self, length: Length) -> Result<Self, Error> {
+        self.0
+            .checked_add(length.0 as u64)
+            .map(Self)
+            .ok_or(Error::Overflow)
     }
 
     /// Get the offset value.
@@ -216,6 +605,25 @@ impl core::convert::From<Offset> for u64 {
     }
 }
 
+/// Add a length to the offset.
+///
+/// # Panics
+/// Panics if the result is not representable.
+/// See [`Offset::checked_add`] for a non-panicking alternative.
+impl core::ops::Add<Length> for Offset {
+    type Output = Offset;
+
+    fn add(self, length: Length) -> Self::Output {
+        self.checked_add(length).expect("offset overflow")
+    }
+}
+
+impl core::ops::AddAssign<Length> for Offset {
+    fn add_assign(&mut self, length: Length) {
+        *self = *self + length;
+    }
+}
+
 /// A length of a view into an object.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Length(usize);
@@ -227,32 +635,48 @@ impl Length {
         GRANULARITY.get_or_init(map_impl::length_granularity).get()
     }
 
+    /// Returns `true` if the value is a multiple of [`granularity`](`Self::granularity`).
+    pub fn is_aligned(value: usize) -> bool {
+        super::is_aligned(value as u64, Self::granularity() as u64)
+    }
+
     /// Create a length with the specified value.
     ///
-    /// If the value is not a multiple of [`granularity`](`Self::granularity`), returns `None`.
-    pub fn exact(value: usize) -> Option<Self> {
-        assert!(value != 0, "length must not be zero");
-        if value % Self::granularity() == 0 {
-            Some(Self(value))
+    /// If the value is zero, returns [`Error::ZeroLength`].
+    /// If the value is not a multiple of [`granularity`](`Self::granularity`), returns
+    /// [`Error::Misaligned`].
+    pub fn exact(value: usize) -> Result<Self, Error> {
+        if value == 0 {
+            Err(Error::ZeroLength)
+        } else if Self::is_aligned(value) {
+            Ok(Self(value))
         } else {
-            None
+            Err(Error::Misaligned)
         }
     }
 
     /// Create a length, rounded up to the next possible value.
-    pub fn round_up(value: usize) -> Self {
-        assert!(value != 0, "length must not be zero");
-        Self::exact(
-            value.checked_add(Self::granularity() - 1).unwrap() / Self::granularity()
-                * Self::granularity(),
-        )
-        .unwrap()
+    ///
+    /// If the value is zero, returns [`Error::ZeroLength`].
+    /// If the rounded value is not representable, returns [`Error::Overflow`].
+    pub fn round_up(value: usize) -> Result<Self, Error> {
+        let granularity = Self::granularity();
+        let value = value.checked_add(granularity - 1).ok_or(Error::Overflow)?;
+        Self::exact(value / granularity * granularity)
     }
 
     /// Create a length, rounded down to the next possible value.
-    pub fn round_down(value: usize) -> Self {
-        assert!(value != 0, "length must not be zero");
-        Self::exact(value / Self::granularity() * Self::granularity()).unwrap()
+    ///
+    /// If the rounded value is zero, returns [`Error::ZeroLength`].
+    pub fn round_down(value: usize) -> Result<Self, Error> {
+        Self::exact(value / Self::granularity() * Self::granularity())
+    }
+
+    /// Add two lengths.
+    ///
+    /// If the result is not representable, returns [`Error::Overflow`].
+    pub fn checked_add(self, other: Length) -> Result<Self, Error> {
+        self.0.checked_add(other.0).map(Self).ok_or(Error::Overflow)
     }
 
     /// Get the length value.
@@ -267,12 +691,33 @@ impl core::convert::From<Length> for usize {
     }
 }
 
+/// Add two lengths.
+///
+/// # Panics
+/// Panics if the result is not representable.
+/// See [`Length::checked_add`] for a non-panicking alternative.
+impl core::ops::Add for Length {
+    type Output = Length;
+
+    fn add(self, other: Length) -> Self::Output {
+        self.checked_add(other).expect("length overflow")
+    }
+}
+
+impl core::ops::AddAssign for Length {
+    fn add_assign(&mut self, other: Length) {
+        *self = *self + other;
+    }
+}
+
 /// A view of an object.
 #[derive(Copy, Clone, Debug)]
 pub struct View<'a> {
     pub(crate) offset: Offset,
     pub(crate) length: Length,
     pub(crate) execute: bool,
+    pub(crate) permissions: FilePermissions,
+    pub(crate) huge_pages: Option<HugePageSize>,
     pub(crate) object: &'a map_impl::Object,
 }
 
@@ -282,5 +727,221 @@ pub struct ViewMut<'a> {
     pub(crate) offset: Offset,
     pub(crate) length: Length,
     pub(crate) copy_on_write: bool,
+    pub(crate) permissions: FilePermissions,
+    pub(crate) huge_pages: Option<HugePageSize>,
     pub(crate) object: &'a map_impl::Object,
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::raw::{map_mut, page_size};
+    use std::os::unix::io::{FromRawFd, IntoRawFd, OwnedFd};
+
+    #[test]
+    fn offset() {
+        let granularity = Offset::granularity();
+        assert_eq!(Offset::exact(0).unwrap().to_u64(), 0);
+        assert_eq!(Offset::exact(granularity).unwrap().to_u64(), granularity);
+        assert!(matches!(Offset::exact(1), Err(Error::Misaligned)));
+        assert!(matches!(Offset::exact(u64::MAX), Err(Error::Misaligned)));
+
+        assert_eq!(Offset::round_up(0).unwrap().to_u64(), 0);
+        assert_eq!(Offset::round_up(1).unwrap().to_u64(), granularity);
+        assert_eq!(Offset::round_up(granularity).unwrap().to_u64(), granularity);
+        assert!(matches!(Offset::round_up(u64::MAX), Err(Error::Overflow)));
+
+        assert_eq!(Offset::round_down(0).to_u64(), 0);
+        assert_eq!(Offset::round_down(granularity - 1).to_u64(), 0);
+        assert_eq!(Offset::round_down(granularity).to_u64(), granularity);
+        assert_eq!(
+            Offset::round_down(u64::MAX).to_u64(),
+            u64::MAX - (granularity - 1)
+        );
+    }
+
+    #[test]
+    fn length() {
+        let granularity = Length::granularity();
+        assert!(matches!(Length::exact(0), Err(Error::ZeroLength)));
+        assert_eq!(Length::exact(granularity).unwrap().to_usize(), granularity);
+        assert!(matches!(Length::exact(1), Err(Error::Misaligned)));
+        assert!(matches!(Length::exact(usize::MAX), Err(Error::Misaligned)));
+
+        assert!(matches!(Length::round_up(0), Err(Error::ZeroLength)));
+        assert_eq!(Length::round_up(1).unwrap().to_usize(), granularity);
+        assert_eq!(
+            Length::round_up(granularity).unwrap().to_usize(),
+            granularity
+        );
+        assert!(matches!(Length::round_up(usize::MAX), Err(Error::Overflow)));
+
+        assert!(matches!(Length::round_down(0), Err(Error::ZeroLength)));
+        assert!(matches!(
+            Length::round_down(granularity - 1),
+            Err(Error::ZeroLength)
+        ));
+        assert_eq!(
+            Length::round_down(granularity + 1).unwrap().to_usize(),
+            granularity
+        );
+        assert_eq!(
+            Length::round_down(usize::MAX).unwrap().to_usize(),
+            usize::MAX - (granularity - 1)
+        );
+    }
+
+    #[test]
+    fn add() {
+        let length = Length::exact(Length::granularity()).unwrap();
+        let zero = Offset::exact(0).unwrap();
+        assert_eq!(zero + length, zero.checked_add(length).unwrap());
+        assert_eq!(
+            (zero + length + length).to_u64(),
+            2 * length.to_usize() as u64
+        );
+        let mut offset = zero;
+        offset += length;
+        assert_eq!(offset.to_u64(), length.to_usize() as u64);
+        assert_eq!((length + length).to_usize(), 2 * length.to_usize());
+        let mut sum = length;
+        sum += length;
+        assert_eq!(sum, length + length);
+
+        let max_offset = Offset::round_down(u64::MAX);
+        assert!(matches!(
+            max_offset.checked_add(length),
+            Err(Error::Overflow)
+        ));
+        let max_length = Length::round_down(usize::MAX).unwrap();
+        assert!(matches!(
+            max_length.checked_add(length),
+            Err(Error::Overflow)
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "offset overflow")]
+    fn add_overflow() {
+        let _ = Offset::round_down(u64::MAX) + Length::exact(Length::granularity()).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn seal() {
+        let length = Length::exact(page_size()).unwrap();
+        let offset = Offset::exact(0).unwrap();
+        let seals = Seals {
+            shrink: true,
+            write: true,
+            ..Seals::default()
+        };
+
+        let mut object = Object::anonymous(page_size(), ReadPermissions::Read).unwrap();
+        assert!(object.seal(seals).is_err());
+        assert!(!object.seals().unwrap().write);
+        assert!(object.permissions().write);
+
+        let mut object = Object::anonymous_sealed(page_size(), ReadPermissions::Read).unwrap();
+        object.seal(seals).unwrap();
+        assert_eq!(object.seals().unwrap(), seals);
+        assert!(!object.permissions().write);
+        assert!(matches!(
+            object.view_mut(offset, length, WritePermissions::Write),
+            Err(Error::PermissionDenied)
+        ));
+        let view = object
+            .view_mut(offset, length, WritePermissions::CopyOnWrite)
+            .unwrap();
+        // Safety: the copy isn't shared
+        let mut mapping = unsafe { map_mut(&view) }.unwrap();
+        mapping[0] = 1;
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn from_sealed_fd() {
+        let read = FilePermissions {
+            write: false,
+            execute: false,
+        };
+
+        // Unsealed objects are rejected
+        let object = Object::anonymous(page_size(), ReadPermissions::Read).unwrap();
+        let err = Object::from_sealed_fd(OwnedFd::from(object), read).unwrap_err();
+        assert!(matches!(err, Error::NotSealed));
+
+        // Objects must be sealed against both shrinking and writing
+        for seals in [
+            Seals {
+                shrink: true,
+                ..Seals::default()
+            },
+            Seals {
+                write: true,
+                ..Seals::default()
+            },
+        ] {
+            let mut object = Object::anonymous_sealed(page_size(), ReadPermissions::Read).unwrap();
+            object.seal(seals).unwrap();
+            let err = Object::from_sealed_fd(OwnedFd::from(object), read).unwrap_err();
+            assert!(matches!(err, Error::NotSealed));
+        }
+
+        let seals = Seals {
+            shrink: true,
+            write: true,
+            ..Seals::default()
+        };
+        let mut object = Object::anonymous_sealed(page_size(), ReadPermissions::Read).unwrap();
+        object.seal(seals).unwrap();
+        let fd = OwnedFd::from(object);
+        let duplicate = fd.try_clone().unwrap();
+        let object = Object::from_sealed_fd(fd, read).unwrap();
+        assert_eq!(object.size(), page_size() as u64);
+        assert!(!object.permissions().write);
+
+        let write = FilePermissions {
+            write: true,
+            execute: false,
+        };
+        let err = Object::from_sealed_fd(duplicate, write).unwrap_err();
+        assert!(matches!(err, Error::PermissionDenied));
+    }
+
+    #[test]
+    fn fd_round_trip() {
+        let object = Object::anonymous(1, ReadPermissions::Read).unwrap();
+        // Safety: the descriptor is owned by the object
+        let object = unsafe { Object::from_raw_fd(object.into_raw_fd()) };
+        assert_eq!(object.size(), 1);
+        assert!(object.permissions().write);
+        assert_eq!(object.huge_page_size(), None);
+
+        // Safety: the duplicate is only used to create another object, which isn't truncated
+        let fd = unsafe { object.borrow_fd() }.try_clone_to_owned().unwrap();
+        let permissions = FilePermissions {
+            write: false,
+            execute: false,
+        };
+        // Safety: neither object is truncated
+        let duplicate = unsafe { Object::from_owned_fd(fd, permissions) }.unwrap();
+        assert_eq!(duplicate.size(), 1);
+    }
+
+    #[test]
+    fn huge_fd_round_trip() {
+        let page_size = HugePageSize::Size2MiB;
+        let object = match Object::anonymous_huge(1, page_size, ReadPermissions::Read) {
+            Ok(object) => object,
+            // Huge pages may not be configured in the test environment
+            Err(_) => return,
+        };
+        assert_eq!(object.size(), page_size.size() as u64);
+        let permissions = object.permissions();
+        // Safety: the descriptor is owned by the object, and isn't shared
+        let object = unsafe { Object::from_owned_fd(OwnedFd::from(object), permissions) }.unwrap();
+        assert_eq!(object.huge_page_size(), Some(page_size));
+        assert_eq!(object.size(), page_size.size() as u64);
+    }
+}
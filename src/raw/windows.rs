@@ -1,25 +1,63 @@
-use crate::raw::{Length, Offset, View, ViewMut};
-use std::{convert::TryInto, io::Error, num::NonZeroUsize};
+use crate::raw::{
+    next_object_id, Advice, Error, HugePageSize, Length, MapOptions, Offset, Protection, Seals,
+    View, ViewMut,
+};
+use std::{
+    convert::TryInto,
+    num::NonZeroUsize,
+    sync::{PoisonError, RwLock},
+};
 use winapi::{
-    shared::minwindef::DWORD,
+    shared::{
+        minwindef::DWORD,
+        winerror::{
+            ERROR_ALREADY_EXISTS, ERROR_COMMITMENT_LIMIT, ERROR_INVALID_ADDRESS,
+            ERROR_NOT_ENOUGH_MEMORY, ERROR_OUTOFMEMORY, ERROR_WORKING_SET_QUOTA,
+        },
+    },
     um::{
-        handleapi::INVALID_HANDLE_VALUE,
+        errhandlingapi::GetLastError,
+        handleapi::{CloseHandle, INVALID_HANDLE_VALUE},
         memoryapi::{
-            CreateFileMappingW, MapViewOfFileEx, UnmapViewOfFile, VirtualAlloc, VirtualFree,
-            FILE_MAP_ALL_ACCESS, FILE_MAP_COPY, FILE_MAP_EXECUTE, FILE_MAP_READ,
+            CreateFileMappingW, FlushViewOfFile, MapViewOfFile, MapViewOfFileEx, OpenFileMappingW,
+            PrefetchVirtualMemory, UnmapViewOfFile, VirtualAlloc, VirtualFree, VirtualLock,
+            VirtualProtect, VirtualQuery, VirtualUnlock, FILE_MAP_ALL_ACCESS, FILE_MAP_COPY,
+            FILE_MAP_EXECUTE, FILE_MAP_READ, FILE_MAP_WRITE, WIN32_MEMORY_RANGE_ENTRY,
         },
+        processthreadsapi::{FlushInstructionCache, GetCurrentProcess},
         sysinfoapi::{GetSystemInfo, SYSTEM_INFO},
         winnt::{
-            HANDLE, MEM_RELEASE, MEM_RESERVE, PAGE_EXECUTE_READ, PAGE_EXECUTE_READWRITE,
-            PAGE_NOACCESS, PAGE_READONLY, PAGE_READWRITE, SEC_COMMIT,
+            HANDLE, MEMORY_BASIC_INFORMATION, MEM_COMMIT, MEM_DECOMMIT, MEM_RELEASE, MEM_RESERVE,
+            PAGE_EXECUTE_READ, PAGE_EXECUTE_READWRITE, PAGE_NOACCESS, PAGE_READONLY,
+            PAGE_READWRITE, PAGE_WRITECOPY, SEC_COMMIT,
         },
     },
 };
 
+pub fn is_out_of_memory(syscall: &str, code: i32) -> bool {
+    matches!(
+        syscall,
+        "CreateFileMappingW" | "MapViewOfFile" | "MapViewOfFileEx" | "VirtualAlloc"
+    ) && [
+        ERROR_COMMITMENT_LIMIT,
+        ERROR_NOT_ENOUGH_MEMORY,
+        ERROR_OUTOFMEMORY,
+    ]
+    .iter()
+    .any(|&error| code == error as i32)
+}
+
+pub fn file_size(file: &std::fs::File) -> Result<u64, Error> {
+    file.metadata()
+        .map(|metadata| metadata.len())
+        .map_err(|err| Error::from_os_error("GetFileInformationByHandle", err))
+}
+
 trait ViewImpl {
     fn offset(&self) -> Offset;
     fn length(&self) -> Length;
     fn access_flags(&self) -> DWORD;
+    fn copy_on_write(&self) -> bool;
     fn object(&self) -> &Object;
 }
 
@@ -40,6 +78,10 @@ impl<'a> ViewImpl for View<'a> {
         }
     }
 
+    fn copy_on_write(&self) -> bool {
+        false
+    }
+
     fn object(&self) -> &Object {
         self.object
     }
@@ -62,6 +104,10 @@ impl<'a> ViewImpl for ViewMut<'a> {
         }
     }
 
+    fn copy_on_write(&self) -> bool {
+        self.copy_on_write
+    }
+
     fn object(&self) -> &Object {
         self.object
     }
@@ -69,7 +115,13 @@ impl<'a> ViewImpl for ViewMut<'a> {
 
 #[derive(Debug)]
 pub struct Object {
-    handle: HANDLE,
+    // Replaced when the object is resized, so it is locked while views are mapped
+    handle: RwLock<HANDLE>,
+    // Flushing file buffers requires the file itself, rather than the mapping object
+    file: Option<std::fs::File>,
+    // Page protection of the mapping object, for recreating it when resized
+    access: DWORD,
+    id: u64,
 }
 
 fn split_dword<T>(value: T) -> (DWORD, DWORD)
@@ -89,31 +141,143 @@ where
     )
 }
 
+impl Drop for Object {
+    fn drop(&mut self) {
+        let handle = *self
+            .handle
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        // Safety: handle is valid
+        unsafe {
+            CloseHandle(handle);
+        }
+    }
+}
+
+fn wide_name(name: &str) -> Result<Vec<u16>, Error> {
+    if name.contains('\0') {
+        return Err(Error::InvalidName);
+    }
+    Ok(name.encode_utf16().chain(core::iter::once(0)).collect())
+}
+
 impl Object {
-    pub fn anonymous(size: usize, execute: bool) -> Result<Self, Error> {
+    // The name must be null, or a nul-terminated string.
+    unsafe fn create(size: usize, execute: bool, name: *const u16) -> Result<Self, Error> {
         let access = if execute {
             PAGE_EXECUTE_READWRITE | SEC_COMMIT
         } else {
             PAGE_READWRITE | SEC_COMMIT
         };
         let (size_hi, size_lo) = split_dword(size);
+        let handle = CreateFileMappingW(
+            INVALID_HANDLE_VALUE,
+            core::ptr::null_mut(),
+            access,
+            size_hi,
+            size_lo,
+            name,
+        );
+        if handle.is_null() {
+            Err(Error::last_os_error("CreateFileMappingW"))
+        } else {
+            Ok(Self {
+                handle: RwLock::new(handle),
+                file: None,
+                access,
+                id: next_object_id(),
+            })
+        }
+    }
+
+    pub fn anonymous(size: usize, execute: bool) -> Result<Self, Error> {
         // Safety:
         // Fulfills API expectations.
-        let handle = unsafe {
-            CreateFileMappingW(
-                INVALID_HANDLE_VALUE,
-                core::ptr::null_mut(),
-                access,
-                size_hi,
-                size_lo,
-                core::ptr::null_mut(),
-            )
-        };
-        if handle == INVALID_HANDLE_VALUE {
-            Err(Error::last_os_error())
+        unsafe { Self::create(size, execute, core::ptr::null()) }
+    }
+
+    pub fn anonymous_sealed(_size: usize, _execute: bool) -> Result<Self, Error> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn add_seals(&self, _seals: Seals) -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+
+    // Objects that don't support sealing have no seals
+    pub fn seals(&self) -> Result<Seals, Error> {
+        Ok(Seals::default())
+    }
+
+    pub fn create_named(name: &str, size: usize, execute: bool, _mode: u32) -> Result<Self, Error> {
+        let name = wide_name(name)?;
+        // Safety: the name is nul-terminated
+        let object = unsafe { Self::create(size, execute, name.as_ptr())? };
+        // An existing object is opened rather than created
+        // Safety: always safe to call
+        if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+            Err(Error::Os {
+                syscall: "CreateFileMappingW",
+                code: ERROR_ALREADY_EXISTS as i32,
+            })
         } else {
-            Ok(Self { handle })
+            Ok(object)
+        }
+    }
+
+    pub fn open_named(name: &str, write: bool, execute: bool) -> Result<(Self, u64), Error> {
+        let name = wide_name(name)?;
+        let mut access = FILE_MAP_READ;
+        if write {
+            access |= FILE_MAP_WRITE;
+        }
+        if execute {
+            access |= FILE_MAP_EXECUTE;
+        }
+        // Safety: the name is nul-terminated
+        let handle = unsafe { OpenFileMappingW(access, 0, name.as_ptr()) };
+        if handle.is_null() {
+            return Err(Error::last_os_error("OpenFileMappingW"));
         }
+        let object = Self {
+            handle: RwLock::new(handle),
+            file: None,
+            access: 0,
+            id: next_object_id(),
+        };
+
+        // The size of the object is only available by mapping it, rounded up to the page size
+        // Safety: the entire object is mapped, queried, and unmapped
+        unsafe {
+            let ptr = MapViewOfFile(handle, FILE_MAP_READ, 0, 0, 0);
+            if ptr.is_null() {
+                return Err(Error::last_os_error("MapViewOfFile"));
+            }
+            let mut info = core::mem::MaybeUninit::<MEMORY_BASIC_INFORMATION>::uninit();
+            let result = VirtualQuery(
+                ptr,
+                info.as_mut_ptr(),
+                core::mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+            );
+            UnmapViewOfFile(ptr);
+            if result == 0 {
+                return Err(Error::last_os_error("VirtualQuery"));
+            }
+            Ok((object, info.assume_init().RegionSize as u64))
+        }
+    }
+
+    pub fn unlink_named(name: &str) -> Result<(), Error> {
+        // Named objects are destroyed when the last handle is closed
+        wide_name(name).map(|_| ())
+    }
+
+    pub fn anonymous_huge(
+        _size: usize,
+        _page_size: HugePageSize,
+        _execute: bool,
+    ) -> Result<Self, Error> {
+        Err(Error::Unsupported)
     }
 
     pub unsafe fn with_file(
@@ -128,19 +292,101 @@ impl Object {
             (false, true) => PAGE_EXECUTE_READ,
             (true, true) => PAGE_EXECUTE_READWRITE,
         };
+        let file = file
+            .try_clone()
+            .map_err(|err| Error::from_os_error("DuplicateHandle", err))?;
         let (size_hi, size_lo) = split_dword(size);
         let handle = CreateFileMappingW(
-            std::os::windows::io::AsRawHandle::as_raw_handle(file) as *mut _,
+            std::os::windows::io::AsRawHandle::as_raw_handle(&file) as *mut _,
             core::ptr::null_mut(),
             access,
             size_hi,
             size_lo,
             core::ptr::null_mut(),
         );
-        if handle == INVALID_HANDLE_VALUE {
-            Err(Error::last_os_error())
+        if handle.is_null() {
+            Err(Error::last_os_error("CreateFileMappingW"))
         } else {
-            Ok(Self { handle })
+            Ok(Self {
+                handle: RwLock::new(handle),
+                file: Some(file),
+                access,
+                id: next_object_id(),
+            })
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn set_len(&self, size: u64) -> Result<(), Error> {
+        // Sections not backed by a file can't be resized
+        let file = self.file.as_ref().ok_or(Error::Unsupported)?;
+        let mut current = self.handle.write().unwrap_or_else(PoisonError::into_inner);
+        file.set_len(size)
+            .map_err(|err| Error::from_os_error("SetFileInformationByHandle", err))?;
+        let (size_hi, size_lo) = split_dword(size);
+        // Safety: the file handle is valid
+        let handle = unsafe {
+            CreateFileMappingW(
+                std::os::windows::io::AsRawHandle::as_raw_handle(file) as *mut _,
+                core::ptr::null_mut(),
+                self.access,
+                size_hi,
+                size_lo,
+                core::ptr::null_mut(),
+            )
+        };
+        if handle.is_null() {
+            return Err(Error::last_os_error("CreateFileMappingW"));
+        }
+        // Existing views keep the previous mapping object alive
+        // Safety: handle is valid
+        unsafe {
+            CloseHandle(*current);
+        }
+        *current = handle;
+        Ok(())
+    }
+
+    pub fn sync_all(&self) -> Result<(), Error> {
+        match &self.file {
+            Some(file) => file
+                .sync_all()
+                .map_err(|err| Error::from_os_error("FlushFileBuffers", err)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn handle(&self) -> Result<Handle, Error> {
+        let file = match &self.file {
+            Some(file) => Some(
+                file.try_clone()
+                    .map_err(|err| Error::from_os_error("DuplicateHandle", err))?,
+            ),
+            None => None,
+        };
+        Ok(Handle { file })
+    }
+}
+
+// A duplicate handle of a mapped file, for synchronizing it after the object is dropped.
+#[derive(Debug)]
+pub struct Handle {
+    file: Option<std::fs::File>,
+}
+
+impl Handle {
+    // Called after a synchronous flush.
+    // Flushing a view only starts writing the modifications, so this waits for them to reach
+    // storage, which always writes metadata as well.
+    pub fn sync(&self, _metadata: bool) -> Result<(), Error> {
+        match &self.file {
+            Some(file) => file
+                .sync_all()
+                .map_err(|err| Error::from_os_error("FlushFileBuffers", err)),
+            None => Ok(()),
         }
     }
 }
@@ -155,6 +401,10 @@ fn system_info() -> SYSTEM_INFO {
     }
 }
 
+pub fn page_size() -> NonZeroUsize {
+    NonZeroUsize::new(system_info().dwPageSize.try_into().unwrap()).unwrap()
+}
+
 pub fn offset_granularity() -> NonZeroUsize {
     NonZeroUsize::new(system_info().dwPageSize.try_into().unwrap()).unwrap()
 }
@@ -163,12 +413,34 @@ pub fn length_granularity() -> NonZeroUsize {
     NonZeroUsize::new(system_info().dwAllocationGranularity.try_into().unwrap()).unwrap()
 }
 
-// Must take care with the pointer provided.  The pointer must be null, or must point to a
-// reserved virtual memory region that was previously allocated and freed.
+pub fn check_options(options: &MapOptions) -> Result<(), Error> {
+    if options.huge_pages.is_some() {
+        Err(Error::Unsupported)
+    } else {
+        Ok(())
+    }
+}
+
+// Converts a failure to map at the address hint to `AddressUnavailable`.
+fn placement_error(err: Error) -> Error {
+    match err {
+        Error::Os { code, .. } if code == ERROR_INVALID_ADDRESS as i32 => Error::AddressUnavailable,
+        err => err,
+    }
+}
+
+// Must take care with the pointer provided.  The pointer must be null, an address hint, or must
+// point to a reserved virtual memory region that was previously allocated and freed.
 unsafe fn map_impl<T: ViewImpl>(ptr: *mut u8, view: &T) -> Result<*mut u8, Error> {
     let (offset_hi, offset_lo) = split_dword(u64::from(view.offset()));
+    // The handle can't be closed by a concurrent resize while it's mapped
+    let handle = view
+        .object()
+        .handle
+        .read()
+        .unwrap_or_else(PoisonError::into_inner);
     let addr = MapViewOfFileEx(
-        view.object().handle,
+        *handle,
         view.access_flags(),
         offset_hi,
         offset_lo,
@@ -176,25 +448,79 @@ unsafe fn map_impl<T: ViewImpl>(ptr: *mut u8, view: &T) -> Result<*mut u8, Error
         ptr as *mut _,
     );
     if addr.is_null() {
-        Err(Error::last_os_error())
+        Err(Error::last_os_error("MapViewOfFileEx"))
     } else {
         Ok(addr as *mut u8)
     }
 }
 
-fn map_multiple_impl<T: ViewImpl>(views: &[T]) -> Result<(*mut u8, usize), Error> {
+// Apply the options to each view after it is mapped.
+fn finish_map(
+    ptr: *mut u8,
+    view_lengths: impl Iterator<Item = usize> + Clone,
+    options: &MapOptions,
+) -> Result<(), Error> {
+    if options.lock {
+        let mut offset = 0;
+        for len in view_lengths.clone() {
+            // Safety: the memory was just mapped
+            unsafe {
+                if let Err(err) = lock(ptr.add(offset), len) {
+                    unmap(ptr, view_lengths);
+                    return Err(err);
+                }
+            }
+            offset += len;
+        }
+    }
+    if options.populate {
+        let mut range = WIN32_MEMORY_RANGE_ENTRY {
+            VirtualAddress: ptr as *mut _,
+            NumberOfBytes: view_lengths.sum(),
+        };
+        // Populating is only an optimization, so prefetching is allowed to fail.
+        // Safety: the memory was just mapped
+        unsafe { PrefetchVirtualMemory(GetCurrentProcess(), 1, &mut range, 0) };
+    }
+    Ok(())
+}
+
+fn map_single_impl<T: ViewImpl>(view: &T, options: &MapOptions) -> Result<*mut u8, Error> {
+    let hint = options.address_hint as *mut u8;
+    // Safety: the pointer is either an address hint, which fails rather than replacing existing
+    // mappings, or is selected by the kernel.
+    let ptr = unsafe {
+        match map_impl(hint, view) {
+            Ok(ptr) => ptr,
+            Err(_) if !hint.is_null() && !options.fixed_noreplace => {
+                map_impl(core::ptr::null_mut(), view)?
+            }
+            Err(err) => return Err(placement_error(err)),
+        }
+    };
+    finish_map(ptr, core::iter::once(view.length().into()), options)?;
+    Ok(ptr)
+}
+
+fn map_multiple_impl<T: ViewImpl>(
+    views: &[T],
+    options: &MapOptions,
+) -> Result<(*mut u8, usize), Error> {
     // Allocate mapping
     let len = views
         .iter()
         .fold(0, |length, view| length + usize::from(view.length()));
-    let try_map = || {
+    let try_map = |hint: *mut u8| {
         // Safety:
-        // Pointer is either an available memory region or null. We only deallocate memory
-        // that we immediately allocated.
+        // Pointer is either an available memory region, an address hint, or null. We only
+        // deallocate memory that we immediately allocated.
         let ptr = unsafe {
-            let ptr = VirtualAlloc(core::ptr::null_mut(), len, MEM_RESERVE, PAGE_NOACCESS);
-            if ptr.is_null() || VirtualFree(ptr, 0, MEM_RELEASE) == 0 {
-                return Err(Error::last_os_error());
+            let ptr = VirtualAlloc(hint as *mut _, len, MEM_RESERVE, PAGE_NOACCESS);
+            if ptr.is_null() {
+                return Err(placement_error(Error::last_os_error("VirtualAlloc")));
+            }
+            if VirtualFree(ptr, 0, MEM_RELEASE) == 0 {
+                return Err(Error::last_os_error("VirtualFree"));
             }
             ptr as *mut u8
         };
@@ -214,45 +540,158 @@ fn map_multiple_impl<T: ViewImpl>(views: &[T]) -> Result<(*mut u8, usize), Error
         Ok(ptr)
     };
 
+    // Only the first try uses the address hint, unless the address is required.
+    let mut hint = options.address_hint as *mut u8;
     let mut tries = 0;
     const MAX_TRIES: usize = 10;
-    loop {
+    let ptr = loop {
         tries += 1;
-        match try_map() {
-            Ok(ptr) => break Ok((ptr, len)),
+        match try_map(hint) {
+            Ok(ptr) => break ptr,
+            Err(Error::AddressUnavailable) if options.fixed_noreplace => {
+                return Err(Error::AddressUnavailable)
+            }
             Err(err) => {
                 if tries == MAX_TRIES {
-                    break Err(err);
+                    return Err(err);
                 } else {
+                    if !options.fixed_noreplace {
+                        hint = core::ptr::null_mut();
+                    }
                     continue;
                 }
             }
         }
-    }
+    };
+    finish_map(ptr, views.iter().map(|v| v.length().into()), options)?;
+    Ok((ptr, len))
 }
 
-pub fn map(view: &View<'_>) -> Result<(*const u8, usize), Error> {
+pub fn map(view: &View<'_>, options: &MapOptions) -> Result<(*const u8, usize), Error> {
     Ok((
-        // Safety: the pointer is selected by the kernel.
-        unsafe { map_impl(core::ptr::null_mut(), view)? as *const u8 },
+        map_single_impl(view, options)? as *const u8,
         view.length.into(),
     ))
 }
 
-pub fn map_mut(view: &ViewMut<'_>) -> Result<(*mut u8, usize), Error> {
-    Ok((
-        // Safety: the pointer is selected by the kernel.
-        unsafe { map_impl(core::ptr::null_mut(), view)? },
-        view.length.into(),
-    ))
+pub fn map_mut(view: &ViewMut<'_>, options: &MapOptions) -> Result<(*mut u8, usize), Error> {
+    Ok((map_single_impl(view, options)?, view.length.into()))
+}
+
+pub fn map_multiple(views: &[View<'_>], options: &MapOptions) -> Result<(*const u8, usize), Error> {
+    map_multiple_impl(views, options).map(|(ptr, len)| (ptr as *const u8, len))
+}
+
+pub fn map_multiple_mut(
+    views: &[ViewMut<'_>],
+    options: &MapOptions,
+) -> Result<(*mut u8, usize), Error> {
+    map_multiple_impl(views, options)
+}
+
+// Views can't be resized, so a new view is always mapped.
+// If remapping fails, the original memory map is left unchanged.
+unsafe fn remap_impl<T: ViewImpl>(ptr: *mut u8, len: usize, view: &T) -> Result<*mut u8, Error> {
+    let remapped = map_impl(core::ptr::null_mut(), view)?;
+    if view.copy_on_write() {
+        // Private modifications are only preserved by copying them
+        let _ = protect(ptr, len, Protection::Read, true);
+        core::ptr::copy_nonoverlapping(ptr, remapped, len.min(view.length().into()));
+    }
+    unmap(ptr, core::iter::once(len));
+    Ok(remapped)
+}
+
+pub unsafe fn remap(ptr: *const u8, len: usize, view: &View<'_>) -> Result<*const u8, Error> {
+    remap_impl(ptr as *mut u8, len, view).map(|ptr| ptr as *const u8)
+}
+
+pub unsafe fn remap_mut(ptr: *mut u8, len: usize, view: &ViewMut<'_>) -> Result<*mut u8, Error> {
+    remap_impl(ptr, len, view)
+}
+
+pub fn reserve_address_space(len: usize) -> Result<*mut u8, Error> {
+    // Safety: the system selects an unused region
+    let ptr = unsafe { VirtualAlloc(core::ptr::null_mut(), len, MEM_RESERVE, PAGE_NOACCESS) };
+    if ptr.is_null() {
+        Err(Error::last_os_error("VirtualAlloc"))
+    } else {
+        Ok(ptr as *mut u8)
+    }
+}
+
+pub unsafe fn commit(ptr: *mut u8, len: usize) -> Result<(), Error> {
+    if VirtualAlloc(ptr as *mut _, len, MEM_COMMIT, PAGE_READWRITE).is_null() {
+        Err(Error::last_os_error("VirtualAlloc"))
+    } else {
+        Ok(())
+    }
+}
+
+pub unsafe fn decommit(ptr: *mut u8, len: usize) -> Result<(), Error> {
+    if VirtualFree(ptr as *mut _, len, MEM_DECOMMIT) == 0 {
+        Err(Error::last_os_error("VirtualFree"))
+    } else {
+        Ok(())
+    }
+}
+
+pub unsafe fn release(ptr: *mut u8, _len: usize) {
+    let status = VirtualFree(ptr as *mut _, 0, MEM_RELEASE);
+    debug_assert!(status != 0);
+}
+
+pub fn allocate(len: usize, _sparse: bool) -> Result<*mut u8, Error> {
+    // Safety: the system selects an unused region
+    let ptr = unsafe {
+        VirtualAlloc(
+            core::ptr::null_mut(),
+            len,
+            MEM_RESERVE | MEM_COMMIT,
+            PAGE_READWRITE,
+        )
+    };
+    if ptr.is_null() {
+        Err(Error::last_os_error("VirtualAlloc"))
+    } else {
+        Ok(ptr as *mut u8)
+    }
+}
+
+// Allocations can't be resized in place.
+pub unsafe fn reallocate(
+    _ptr: *mut u8,
+    _old_len: usize,
+    _new_len: usize,
+) -> Result<*mut u8, Error> {
+    Err(Error::Unsupported)
+}
+
+pub unsafe fn discard(ptr: *mut u8, len: usize) -> Result<(), Error> {
+    // Recommitted pages are zeroed
+    decommit(ptr, len)?;
+    commit(ptr, len)
 }
 
-pub fn map_multiple(views: &[View<'_>]) -> Result<(*const u8, usize), Error> {
-    map_multiple_impl(views).map(|(ptr, len)| (ptr as *const u8, len))
+pub unsafe fn deallocate(ptr: *mut u8, len: usize) {
+    release(ptr, len)
 }
 
-pub fn map_multiple_mut(views: &[ViewMut<'_>]) -> Result<(*mut u8, usize), Error> {
-    map_multiple_impl(views)
+// Views can only be mapped into placeholders, which aren't supported by `VirtualAlloc`.
+pub unsafe fn map_fixed(
+    _ptr: *mut u8,
+    _view: &View<'_>,
+    _options: &MapOptions,
+) -> Result<(), Error> {
+    Err(Error::Unsupported)
+}
+
+pub unsafe fn map_fixed_mut(
+    _ptr: *mut u8,
+    _view: &ViewMut<'_>,
+    _options: &MapOptions,
+) -> Result<(), Error> {
+    Err(Error::Unsupported)
 }
 
 pub unsafe fn unmap(mut ptr: *mut u8, view_lengths: impl Iterator<Item = usize>) {
@@ -262,3 +701,76 @@ pub unsafe fn unmap(mut ptr: *mut u8, view_lengths: impl Iterator<Item = usize>)
         ptr = ptr.add(l);
     }
 }
+
+pub unsafe fn protect(
+    ptr: *mut u8,
+    len: usize,
+    protection: Protection,
+    copy_on_write: bool,
+) -> Result<(), Error> {
+    let flags = match protection {
+        Protection::None => PAGE_NOACCESS,
+        Protection::Read => PAGE_READONLY,
+        Protection::ReadWrite if copy_on_write => PAGE_WRITECOPY,
+        Protection::ReadWrite => PAGE_READWRITE,
+        Protection::ReadExecute => PAGE_EXECUTE_READ,
+    };
+    let mut old = 0;
+    if VirtualProtect(ptr as *mut _, len, flags, &mut old) != 0 {
+        Ok(())
+    } else {
+        Err(Error::last_os_error("VirtualProtect"))
+    }
+}
+
+pub unsafe fn flush_instruction_cache(ptr: *const u8, len: usize) {
+    let status = FlushInstructionCache(GetCurrentProcess(), ptr as *const _, len);
+    debug_assert!(status != 0);
+}
+
+pub unsafe fn advise(_ptr: *mut u8, _len: usize, advice: Advice) -> Result<(), Error> {
+    match advice {
+        // Access pattern hints may be ignored
+        Advice::Normal | Advice::Sequential | Advice::Random | Advice::WillNeed => Ok(()),
+        _ => Err(Error::Unsupported),
+    }
+}
+
+// Flushing a view never waits for the writes to complete, so synchronous flushes must also sync the
+// file with `Handle::sync`.
+pub unsafe fn flush(ptr: *mut u8, len: usize, _asynchronous: bool) -> Result<(), Error> {
+    if FlushViewOfFile(ptr as *const _, len) != 0 {
+        Ok(())
+    } else {
+        Err(Error::last_os_error("FlushViewOfFile"))
+    }
+}
+
+pub unsafe fn lock(ptr: *mut u8, len: usize) -> Result<(), Error> {
+    if VirtualLock(ptr as *mut _, len) != 0 {
+        Ok(())
+    } else {
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() == Some(ERROR_WORKING_SET_QUOTA as i32) {
+            Err(Error::LockLimitExceeded)
+        } else {
+            Err(Error::from_os_error("VirtualLock", err))
+        }
+    }
+}
+
+pub unsafe fn unlock(ptr: *mut u8, len: usize) -> Result<(), Error> {
+    if VirtualUnlock(ptr as *mut _, len) != 0 {
+        Ok(())
+    } else {
+        Err(Error::last_os_error("VirtualUnlock"))
+    }
+}
+
+pub fn lock_all(_current: bool, _future: bool) -> Result<(), Error> {
+    Err(Error::Unsupported)
+}
+
+pub fn unlock_all() -> Result<(), Error> {
+    Err(Error::Unsupported)
+}
@@ -0,0 +1,308 @@
+use crate::Mirror;
+use std::sync::{
+    atomic::{self, AtomicUsize, Ordering},
+    Arc,
+};
+
+// Positions are stored modulo twice the capacity, which distinguishes a full ring from an empty
+// one without requiring the capacity to be a power of two.
+struct Shared<T> {
+    ptr: *mut T,
+    capacity: usize,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    _mirror: Mirror<T>,
+}
+
+impl<T> Shared<T> {
+    fn index(&self, position: usize) -> usize {
+        if position >= self.capacity {
+            position - self.capacity
+        } else {
+            position
+        }
+    }
+
+    fn advance(&self, position: usize, count: usize) -> usize {
+        (position + count) % (2 * self.capacity)
+    }
+
+    fn distance(&self, head: usize, tail: usize) -> usize {
+        if tail >= head {
+            tail - head
+        } else {
+            tail + 2 * self.capacity - head
+        }
+    }
+}
+
+// Checking if the other half has been dropped must synchronize with its final accesses, which are
+// released when its reference count is decremented.
+fn is_abandoned<T>(shared: &Arc<Shared<T>>) -> bool {
+    let abandoned = Arc::strong_count(shared) == 1;
+    atomic::fence(Ordering::Acquire);
+    abandoned
+}
+
+/// A single-producer, single-consumer ring buffer.
+///
+/// The ring is backed by a [`Mirror`], so the readable and writable regions are always
+/// contiguous, even when they wrap around the end of the buffer.
+///
+/// Every slot holds a value owned by the mirror.
+/// Pushing a value drops the value previously in its slot, and consuming a value leaves it in its
+/// slot, so values remaining in the ring are dropped along with the mirror.
+pub struct MirrorRing<T> {
+    mirror: Mirror<T>,
+}
+
+impl<T> MirrorRing<T> {
+    /// Create an empty ring buffer from a mirrored memory region.
+    ///
+    /// The existing values in the mirror are treated as unused slots.
+    pub fn new(mirror: Mirror<T>) -> Self {
+        Self { mirror }
+    }
+
+    /// The maximum number of elements the ring can hold.
+    pub fn capacity(&self) -> usize {
+        self.mirror.len() / 2
+    }
+
+    /// Split the ring into its producer and consumer halves.
+    pub fn split(mut self) -> (Producer<T>, Consumer<T>) {
+        let shared = Arc::new(Shared {
+            ptr: self.mirror.as_mut_ptr(),
+            capacity: self.capacity(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            _mirror: self.mirror,
+        });
+        (
+            Producer {
+                shared: shared.clone(),
+            },
+            Consumer { shared },
+        )
+    }
+}
+
+impl<T> From<Mirror<T>> for MirrorRing<T> {
+    fn from(mirror: Mirror<T>) -> Self {
+        Self::new(mirror)
+    }
+}
+
+/// The writing half of a [`MirrorRing`].
+pub struct Producer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+// Safety: the producer only accesses the slots it has exclusive access to
+unsafe impl<T: Send> Send for Producer<T> {}
+unsafe impl<T: Sync> Sync for Producer<T> {}
+
+impl<T> Producer<T> {
+    /// The maximum number of elements the ring can hold.
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity
+    }
+
+    /// The number of elements waiting to be consumed.
+    pub fn len(&self) -> usize {
+        let head = self.shared.head.load(Ordering::Acquire);
+        let tail = self.shared.tail.load(Ordering::Relaxed);
+        self.shared.distance(head, tail)
+    }
+
+    /// Returns `true` if there are no elements waiting to be consumed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if there is no space to write elements.
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    /// Returns `true` if the consumer has been dropped.
+    pub fn is_abandoned(&self) -> bool {
+        is_abandoned(&self.shared)
+    }
+
+    /// Returns the contiguous region of slots available for writing.
+    ///
+    /// Values written to the window are made available to the consumer by
+    /// [`commit`](`Self::commit`).
+    pub fn write_window(&mut self) -> &mut [T] {
+        let tail = self.shared.tail.load(Ordering::Relaxed);
+        let free = self.capacity() - self.len();
+        // Safety: the consumer never accesses slots between the tail and the head, and the mirror
+        // makes up to `capacity` slots past any index contiguous
+        unsafe {
+            std::slice::from_raw_parts_mut(self.shared.ptr.add(self.shared.index(tail)), free)
+        }
+    }
+
+    /// Make the first `count` elements of the write window available to the consumer.
+    ///
+    /// # Panics
+    /// Panics if `count` is larger than the write window.
+    pub fn commit(&mut self, count: usize) {
+        assert!(
+            count <= self.capacity() - self.len(),
+            "commit exceeds the write window"
+        );
+        let tail = self.shared.tail.load(Ordering::Relaxed);
+        self.shared
+            .tail
+            .store(self.shared.advance(tail, count), Ordering::Release);
+    }
+
+    /// Write a single value to the ring.
+    ///
+    /// Returns the value if the ring is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        match self.write_window().first_mut() {
+            Some(slot) => {
+                *slot = value;
+                self.commit(1);
+                Ok(())
+            }
+            None => Err(value),
+        }
+    }
+}
+
+/// The reading half of a [`MirrorRing`].
+pub struct Consumer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+// Safety: the consumer only accesses the slots it has exclusive access to
+unsafe impl<T: Send> Send for Consumer<T> {}
+unsafe impl<T: Sync> Sync for Consumer<T> {}
+
+impl<T> Consumer<T> {
+    /// The maximum number of elements the ring can hold.
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity
+    }
+
+    /// The number of elements available to read.
+    pub fn len(&self) -> usize {
+        let head = self.shared.head.load(Ordering::Relaxed);
+        let tail = self.shared.tail.load(Ordering::Acquire);
+        self.shared.distance(head, tail)
+    }
+
+    /// Returns `true` if there are no elements available to read.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the producer has been dropped.
+    ///
+    /// Once this returns `true`, every element committed by the producer is available to read.
+    pub fn is_abandoned(&self) -> bool {
+        is_abandoned(&self.shared)
+    }
+
+    /// Returns the contiguous region of elements available for reading.
+    ///
+    /// Elements are released back to the producer by [`consume`](`Self::consume`).
+    pub fn read_window(&self) -> &[T] {
+        let head = self.shared.head.load(Ordering::Relaxed);
+        let len = self.len();
+        // Safety: the producer never accesses slots between the head and the tail, and the mirror
+        // makes up to `capacity` slots past any index contiguous
+        unsafe { std::slice::from_raw_parts(self.shared.ptr.add(self.shared.index(head)), len) }
+    }
+
+    /// Release the first `count` elements of the read window back to the producer.
+    ///
+    /// # Panics
+    /// Panics if `count` is larger than the read window.
+    pub fn consume(&mut self, count: usize) {
+        assert!(count <= self.len(), "consume exceeds the read window");
+        let head = self.shared.head.load(Ordering::Relaxed);
+        self.shared
+            .head
+            .store(self.shared.advance(head, count), Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap() {
+        let (mut producer, mut consumer) =
+            MirrorRing::new(Mirror::<u32>::zeroed(1).unwrap()).split();
+        let capacity = producer.capacity();
+        let mut next = 0;
+        let mut expected = 0;
+        for _ in 0..4 {
+            while producer.push(next).is_ok() {
+                next += 1;
+            }
+            assert!(producer.is_full());
+
+            // Consume most of the ring, so the next writes wrap around the end of the buffer
+            let count = capacity - 3;
+            for value in &consumer.read_window()[..count] {
+                assert_eq!(*value, expected);
+                expected += 1;
+            }
+            consumer.consume(count);
+            assert_eq!(producer.write_window().len(), count);
+        }
+        while producer.push(next).is_ok() {
+            next += 1;
+        }
+        let window = consumer.read_window();
+        assert_eq!(window.len(), capacity);
+        for (i, value) in window.iter().enumerate() {
+            assert_eq!(*value, expected + i as u32);
+        }
+    }
+
+    #[test]
+    fn spsc() {
+        const COUNT: u64 = 100_000;
+        let (mut producer, mut consumer) =
+            MirrorRing::new(Mirror::<u64>::zeroed(1).unwrap()).split();
+        let thread = std::thread::spawn(move || {
+            let mut next = 0;
+            while next < COUNT {
+                let window = producer.write_window();
+                let count = window.len().min((COUNT - next) as usize);
+                for slot in &mut window[..count] {
+                    *slot = next;
+                    next += 1;
+                }
+                producer.commit(count);
+            }
+        });
+
+        let mut expected = 0;
+        while expected < COUNT {
+            let window = consumer.read_window();
+            if window.is_empty() {
+                assert!(!consumer.is_abandoned() || !consumer.is_empty());
+                std::thread::yield_now();
+                continue;
+            }
+            for value in window {
+                assert_eq!(*value, expected);
+                expected += 1;
+            }
+            let count = window.len();
+            consumer.consume(count);
+        }
+        thread.join().unwrap();
+        assert!(consumer.is_abandoned());
+        assert!(consumer.is_empty());
+    }
+}
@@ -0,0 +1,305 @@
+use crate::raw::{self, page_size, Length, Reservation};
+use std::{
+    io::Error,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+/// A growable vector whose elements never move.
+///
+/// The vector reserves address space for its maximum capacity up front, and commits memory as it
+/// grows, so pushing never reallocates.
+/// Elements can be pushed through a shared reference, and references obtained with
+/// [`get`](`Self::get`) remain valid across pushes.
+pub struct StableVec<T> {
+    reservation: Reservation,
+    capacity: usize,
+    len: AtomicUsize,
+    // Serializes pushes, and tracks the number of committed bytes
+    committed: Mutex<usize>,
+    _type: PhantomData<T>,
+}
+
+// Safety: elements may be pushed from any thread with a shared reference
+unsafe impl<T: Send> Send for StableVec<T> {}
+unsafe impl<T: Send + Sync> Sync for StableVec<T> {}
+
+impl<T> StableVec<T> {
+    /// Create an empty vector that can hold at least `capacity` elements.
+    ///
+    /// Only address space is reserved, so the capacity may be very large.
+    /// Returns [`raw::Error::Misaligned`] if the alignment of `T` exceeds the page size.
+    pub fn with_capacity(capacity: usize) -> Result<Self, Error> {
+        if std::mem::align_of::<T>() > page_size() {
+            return Err(raw::Error::Misaligned.into());
+        }
+        let size = std::mem::size_of::<T>();
+        let bytes = capacity.checked_mul(size).ok_or(raw::Error::Overflow)?;
+        let reservation = Reservation::new(Length::round_up(bytes.max(1))?)?;
+        Ok(Self {
+            // Zero-sized elements don't use any memory
+            capacity: reservation.len().checked_div(size).unwrap_or(usize::MAX),
+            reservation,
+            len: AtomicUsize::new(0),
+            committed: Mutex::new(0),
+            _type: PhantomData,
+        })
+    }
+
+    /// The maximum number of elements the vector can hold.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of elements in the vector.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+
+    /// Returns `true` if the vector contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn as_ptr(&self) -> *mut T {
+        self.reservation.as_mut_ptr() as *mut T
+    }
+
+    /// Append an element to the vector, returning its index.
+    ///
+    /// Returns an error if the vector is at capacity, or memory can't be committed.
+    pub fn push(&self, value: T) -> Result<usize, Error> {
+        let mut committed = self
+            .committed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let len = self.len.load(Ordering::Relaxed);
+        if len == self.capacity {
+            return Err(raw::Error::OutOfBounds {
+                end: (len as u64 + 1) * std::mem::size_of::<T>() as u64,
+                size: self.reservation.len() as u64,
+            }
+            .into());
+        }
+
+        let end = (len + 1) * std::mem::size_of::<T>();
+        if end > *committed {
+            let end = raw::round_up_to_page(end)?;
+            // Safety: the range is past every element, and views are never mapped
+            unsafe { self.reservation.commit(*committed..end)? };
+            *committed = end;
+        }
+
+        // Safety: the slot is committed, and only written while holding the lock
+        unsafe { self.as_ptr().add(len).write(value) };
+        self.len.store(len + 1, Ordering::Release);
+        Ok(len)
+    }
+
+    /// Get a reference to an element, or `None` if the index is out of bounds.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.as_slice().get(index)
+    }
+
+    /// Get a mutable reference to an element, or `None` if the index is out of bounds.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.as_mut_slice().get_mut(index)
+    }
+
+    /// The elements pushed so far.
+    pub fn as_slice(&self) -> &[T] {
+        // Safety: elements before the length are initialized, and are never moved or dropped
+        // while the vector is shared
+        unsafe { std::slice::from_raw_parts(self.as_ptr(), self.len()) }
+    }
+
+    /// The elements pushed so far, mutably.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        let len = *self.len.get_mut();
+        // Safety: elements before the length are initialized, and uniquely borrowed
+        unsafe { std::slice::from_raw_parts_mut(self.as_ptr(), len) }
+    }
+
+    /// Remove the last element and return it, or `None` if the vector is empty.
+    ///
+    /// Committed memory is retained for future pushes.
+    pub fn pop(&mut self) -> Option<T> {
+        let ptr = self.as_ptr();
+        let len = self.len.get_mut();
+        if *len == 0 {
+            return None;
+        }
+        *len -= 1;
+        // Safety: the element is initialized, and no longer reachable
+        Some(unsafe { ptr.add(*len).read() })
+    }
+
+    /// Remove all elements.
+    ///
+    /// Committed memory is retained for future pushes.
+    pub fn clear(&mut self) {
+        let elements: *mut [T] = self.as_mut_slice();
+        // Elements are leaked rather than dropped twice if dropping panics
+        *self.len.get_mut() = 0;
+        // Safety: the elements are initialized, and no longer reachable
+        unsafe { std::ptr::drop_in_place(elements) };
+    }
+}
+
+impl<T> Drop for StableVec<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T> std::ops::Deref for StableVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+impl<T> std::ops::DerefMut for StableVec<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_slice()
+    }
+}
+
+impl<T> AsRef<[T]> for StableVec<T> {
+    fn as_ref(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T> AsMut<[T]> for StableVec<T> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for StableVec<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn raw_error(err: &Error) -> Option<&raw::Error> {
+        err.get_ref()?.downcast_ref()
+    }
+
+    #[test]
+    fn stable_references() {
+        let vec = StableVec::with_capacity(100_000).unwrap();
+        assert!(vec.capacity() >= 100_000);
+        vec.push(0u64).unwrap();
+        let first = vec.get(0).unwrap() as *const u64;
+        for i in 1..100_000 {
+            assert_eq!(vec.push(i).unwrap(), i as usize);
+        }
+        assert_eq!(vec.get(0).unwrap() as *const u64, first);
+        assert_eq!(vec.len(), 100_000);
+        assert!(vec.iter().copied().eq(0..100_000));
+    }
+
+    #[test]
+    fn full() {
+        let vec = StableVec::with_capacity(1).unwrap();
+        let capacity = vec.capacity();
+        for i in 0..capacity {
+            vec.push(i as u32).unwrap();
+        }
+        let err = vec.push(0).unwrap_err();
+        assert!(matches!(
+            raw_error(&err),
+            Some(raw::Error::OutOfBounds { .. })
+        ));
+        assert_eq!(vec.len(), capacity);
+    }
+
+    #[test]
+    fn concurrent() {
+        const THREADS: usize = 4;
+        const PUSHES: usize = 10_000;
+        let vec = Arc::new(StableVec::with_capacity(THREADS * PUSHES).unwrap());
+        let reader = {
+            let vec = vec.clone();
+            std::thread::spawn(move || {
+                // Every element that is visible is fully written
+                while vec.len() < THREADS * PUSHES {
+                    let len = vec.len();
+                    for i in 0..len {
+                        let (thread, value) = *vec.get(i).unwrap();
+                        assert!(thread < THREADS && value < PUSHES);
+                    }
+                }
+            })
+        };
+        let writers = (0..THREADS)
+            .map(|thread| {
+                let vec = vec.clone();
+                std::thread::spawn(move || {
+                    for value in 0..PUSHES {
+                        vec.push((thread, value)).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        reader.join().unwrap();
+
+        // Each thread's elements are in the order they were pushed
+        let mut next = [0; THREADS];
+        for &(thread, value) in vec.iter() {
+            assert_eq!(value, next[thread]);
+            next[thread] += 1;
+        }
+        assert_eq!(next, [PUSHES; THREADS]);
+    }
+
+    #[test]
+    fn zero_sized() {
+        let mut vec = StableVec::with_capacity(10).unwrap();
+        assert_eq!(vec.capacity(), usize::MAX);
+        for _ in 0..1000 {
+            vec.push(()).unwrap();
+        }
+        assert_eq!(vec.len(), 1000);
+        assert_eq!(vec.pop(), Some(()));
+        assert_eq!(vec.len(), 999);
+    }
+
+    #[test]
+    fn drop_elements() {
+        let counter = Arc::new(());
+        let mut vec = StableVec::with_capacity(10).unwrap();
+        for _ in 0..10 {
+            vec.push(counter.clone()).unwrap();
+        }
+        drop(vec.pop());
+        assert_eq!(Arc::strong_count(&counter), 10);
+        drop(vec);
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn over_aligned() {
+        #[repr(align(65536))]
+        struct Aligned;
+
+        if page_size() < 65536 {
+            let err = StableVec::<Aligned>::with_capacity(1).err().unwrap();
+            assert!(matches!(raw_error(&err), Some(raw::Error::Misaligned)));
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{
+    raw::{self, page_size},
+    ZeroInit,
+};
+use std::{alloc::Layout, io::Error, ptr::NonNull};
+
+// Map zeroed memory for the layout, returning the pointer and the number of bytes mapped.
+fn allocate(layout: Layout) -> Result<(NonNull<u8>, usize), Error> {
+    if layout.align() > page_size() {
+        return Err(raw::Error::Misaligned.into());
+    }
+    if layout.size() == 0 {
+        // Safety: the alignment is never zero
+        return Ok((
+            unsafe { NonNull::new_unchecked(layout.align() as *mut u8) },
+            0,
+        ));
+    }
+    let mapped = raw::round_up_to_page(layout.size())?;
+    Ok((NonNull::new(raw::allocate(mapped, true)?).unwrap(), mapped))
+}
+
+/// A zero-initialized value in its own anonymous memory mapping.
+///
+/// Pages are only backed by physical memory once they are accessed, so large, mostly-zero
+/// values such as counters and bitmaps only use memory for the pages that are touched.
+/// On Linux, swap space is not reserved, so the value may be larger than the available memory.
+pub struct ZeroedBox<T: ?Sized> {
+    ptr: NonNull<T>,
+    mapped: usize,
+}
+
+// Safety: the value is uniquely owned, like a `Box<T>`
+unsafe impl<T: ?Sized + Send> Send for ZeroedBox<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for ZeroedBox<T> {}
+
+impl<T: ZeroInit> ZeroedBox<T> {
+    /// Allocate a zeroed value.
+    ///
+    /// Returns [`raw::Error::Misaligned`] if the alignment of `T` exceeds the page size.
+    pub fn new() -> Result<Self, Error> {
+        let (ptr, mapped) = allocate(Layout::new::<T>())?;
+        Ok(Self {
+            ptr: ptr.cast(),
+            mapped,
+        })
+    }
+}
+
+impl<T: ZeroInit> ZeroedBox<[T]> {
+    /// Allocate a slice of `len` zeroed values.
+    ///
+    /// Returns [`raw::Error::Misaligned`] if the alignment of `T` exceeds the page size.
+    pub fn new_slice(len: usize) -> Result<Self, Error> {
+        let layout = Layout::array::<T>(len).map_err(|_| raw::Error::Overflow)?;
+        let (ptr, mapped) = allocate(layout)?;
+        let slice = core::ptr::slice_from_raw_parts_mut(ptr.as_ptr() as *mut T, len);
+        Ok(Self {
+            ptr: NonNull::new(slice).unwrap(),
+            mapped,
+        })
+    }
+}
+
+/// Allocate a slice of `len` zeroed values.
+///
+/// See [`ZeroedBox`].
+pub fn zeroed_slice<T: ZeroInit>(len: usize) -> Result<ZeroedBox<[T]>, Error> {
+    ZeroedBox::new_slice(len)
+}
+
+impl<T: ?Sized> ZeroedBox<T> {
+    /// Reset the value to zeros.
+    ///
+    /// Rather than writing zeros, the pages are returned to the operating system, and are zeroed
+    /// again when next accessed.
+    /// If the pages can't be released, zeros are written instead.
+    /// The previous value is not dropped.
+    pub fn clear(&mut self) {
+        // Safety: zeros are a valid value, since the box was created with `ZeroInit` contents
+        unsafe {
+            let ptr = self.ptr.as_ptr() as *mut u8;
+            if self.mapped != 0 && raw::discard(ptr, self.mapped).is_err() {
+                core::ptr::write_bytes(ptr, 0, self.mapped);
+            }
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for ZeroedBox<T> {
+    fn drop(&mut self) {
+        // Safety: the value is initialized, and the mapping is owned by the box
+        unsafe {
+            core::ptr::drop_in_place(self.ptr.as_ptr());
+            if self.mapped != 0 {
+                raw::deallocate(self.ptr.as_ptr() as *mut u8, self.mapped);
+            }
+        }
+    }
+}
+
+impl<T: ?Sized> std::ops::Deref for ZeroedBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // Safety: the value is initialized
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: ?Sized> std::ops::DerefMut for ZeroedBox<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: the value is initialized, and uniquely borrowed
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T: ?Sized> AsRef<T> for ZeroedBox<T> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<T: ?Sized> AsMut<T> for ZeroedBox<T> {
+    fn as_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for ZeroedBox<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clear() {
+        let mut slice = zeroed_slice::<u64>(3 * page_size()).unwrap();
+        assert!(slice.iter().all(|x| *x == 0));
+        slice[0] = 1;
+        slice[2 * page_size()] = 2;
+        let last = slice.len() - 1;
+        slice[last] = 3;
+        slice.clear();
+        assert!(slice.iter().all(|x| *x == 0));
+
+        let mut value = ZeroedBox::<u32>::new().unwrap();
+        *value = 5;
+        value.clear();
+        assert_eq!(*value, 0);
+    }
+
+    #[test]
+    fn zero_sized() {
+        #[derive(Debug, PartialEq)]
+        struct Empty;
+        // Safety: a zero-sized type has no bytes
+        unsafe impl ZeroInit for Empty {}
+
+        let mut value = ZeroedBox::<Empty>::new().unwrap();
+        value.clear();
+        assert_eq!(*value, Empty);
+
+        let mut slice = zeroed_slice::<u8>(0).unwrap();
+        slice.clear();
+        assert!(slice.is_empty());
+
+        let slice = zeroed_slice::<Empty>(1000).unwrap();
+        assert_eq!(slice.len(), 1000);
+    }
+
+    #[test]
+    fn over_aligned() {
+        #[derive(Debug)]
+        #[repr(align(65536))]
+        struct Aligned;
+        // Safety: a zero-sized type has no bytes
+        unsafe impl ZeroInit for Aligned {}
+
+        if page_size() < 65536 {
+            let err = ZeroedBox::<Aligned>::new().unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        }
+    }
+}